@@ -131,13 +131,16 @@ if [[ -d "${ROOT_DIR}/codex-rs/codex-litellm-model-session-telemetry" ]]; then
     "${CODEX_DIR}/codex-rs/codex-litellm-model-session-telemetry"
 fi
 
-if [[ -f "${ROOT_DIR}/codex-rs/common/src/litellm.rs" ]]; then
-  if [[ -d "${CODEX_DIR}/codex-rs/common/src" ]]; then
-    cp "${ROOT_DIR}/codex-rs/common/src/litellm.rs" \
-      "${CODEX_DIR}/codex-rs/common/src/litellm.rs"
-  else
-    echo "Skipping legacy common overlay: codex-rs/common/src no longer exists upstream."
-  fi
+if [[ -d "${ROOT_DIR}/codex-rs/codex-litellm-secret-store" ]]; then
+  rm -rf "${CODEX_DIR}/codex-rs/codex-litellm-secret-store"
+  cp -R "${ROOT_DIR}/codex-rs/codex-litellm-secret-store" \
+    "${CODEX_DIR}/codex-rs/codex-litellm-secret-store"
+fi
+
+if [[ -d "${ROOT_DIR}/codex-rs/codex-litellm-gateway" ]]; then
+  rm -rf "${CODEX_DIR}/codex-rs/codex-litellm-gateway"
+  cp -R "${ROOT_DIR}/codex-rs/codex-litellm-gateway" \
+    "${CODEX_DIR}/codex-rs/codex-litellm-gateway"
 fi
 
 if [[ -f "${ROOT_DIR}/codex-rs/tui/src/onboarding/model.rs" ]]; then
@@ -173,6 +176,13 @@ fi
 
 cd "$CODEX_RS_DIR"
 
+# stable-tag.patch has no Cargo.lock entries for the overlay crates or the
+# registry crates only they use. Add those without moving anything the
+# upstream lock already pins, so the --locked build below still holds every
+# upstream dependency at its released version.
+echo "Adding the overlay crates to Cargo.lock..."
+cargo update --workspace
+
 if [[ "$CARGO_CMD" == "cargo" ]]; then
   rustup target add "$TARGET" >/dev/null 2>&1 || true
 fi
@@ -0,0 +1,251 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+/// Names the gateway for runs started without `--gateway`, ahead of
+/// `[litellm] gateway`.
+pub const GATEWAY_ENV: &str = "CODEX_LITELLM_GATEWAY";
+
+/// One `[litellm.gateways.<name>]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct GatewayConfig {
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Environment variable holding this gateway's API key; see
+    /// `api_key_env_var`. When it is unset, the key comes from the secret
+    /// store, saved there by first-run setup.
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    /// Default model on this gateway.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+impl GatewayConfig {
+    /// `api_key_env`, or `LITELLM_API_KEY_<NAME>` with the gateway name in
+    /// upper case and `-` as `_`.
+    pub fn api_key_env_var(&self, name: &str) -> String {
+        self.api_key_env.clone().unwrap_or_else(|| {
+            format!(
+                "LITELLM_API_KEY_{}",
+                name.to_ascii_uppercase().replace('-', "_")
+            )
+        })
+    }
+
+    /// The key from `api_key_env_var`, if set and non-empty.
+    pub fn env_api_key(&self, name: &str) -> Option<String> {
+        std::env::var(self.api_key_env_var(name))
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+/// Named LiteLLM gateways from `config.toml`:
+///
+/// ```toml
+/// [litellm]
+/// gateway = "production"
+///
+/// [litellm.gateways.production]
+/// base_url = "https://litellm.example.com/v1"
+/// model = "gpt-5"
+///
+/// [litellm.gateways.local]
+/// base_url = "http://localhost:4000/v1"
+/// api_key_env = "LOCAL_LITELLM_API_KEY"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct GatewaysConfig {
+    /// `[litellm] gateway`: used when neither `--gateway` nor
+    /// `CODEX_LITELLM_GATEWAY` picks one.
+    #[serde(default, rename = "gateway")]
+    pub default_gateway: Option<String>,
+    #[serde(default)]
+    pub gateways: BTreeMap<String, GatewayConfig>,
+}
+
+impl GatewaysConfig {
+    /// Reads the `[litellm]` table of a `config.toml`. A missing file or
+    /// table means no named gateways.
+    pub fn from_config_toml(path: &Path) -> io::Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        let root: toml::Value = toml::from_str(&contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        match root.get("litellm") {
+            Some(table) => table
+                .clone()
+                .try_into()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// The gateway for this run: `flag` (from `--gateway`), then
+    /// `CODEX_LITELLM_GATEWAY`, then `[litellm] gateway`. `None` means the
+    /// plain `model_providers.litellm`.
+    pub fn selected(&self, flag: Option<&str>) -> Option<String> {
+        flag.map(str::to_string)
+            .or_else(|| std::env::var(GATEWAY_ENV).ok())
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .or_else(|| self.default_gateway.clone())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&GatewayConfig> {
+        self.gateways.get(name)
+    }
+}
+
+/// Makes `name` the `[litellm] gateway` of a `config.toml`, keeping the rest
+/// of the file as written. Runs started without `--gateway` use it.
+pub fn set_default_gateway(path: &Path, name: &str) -> io::Result<()> {
+    validate_gateway_name(name).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let mut document: toml_edit::DocumentMut = contents
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let Some(litellm) = document
+        .entry("litellm")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "`litellm` in config.toml is not a table",
+        ));
+    };
+    litellm.insert("gateway", toml_edit::value(name));
+    std::fs::write(path, document.to_string())
+}
+
+/// Gateway names end up in TOML keys, secret names and model cache keys, so
+/// they are limited to lowercase letters, digits, `-` and `_`.
+pub fn validate_gateway_name(name: &str) -> Result<(), String> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-' || ch == '_')
+    {
+        Ok(())
+    } else {
+        Err(format!(
+            "gateway name `{name}` must be lowercase letters, digits, `-` or `_`"
+        ))
+    }
+}
+
+/// Display name of the `litellm` provider while `gateway` is active. The
+/// models manager and the TUI read the gateway back out of it with
+/// `codex_model_provider_info::litellm_gateway_name`, so the format is fixed.
+pub fn gateway_provider_name(gateway: &str) -> String {
+    format!("LiteLLM ({gateway})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn reads_named_gateways_next_to_other_litellm_settings() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[litellm]
+gateway = "production"
+
+[litellm.secrets]
+backend = "keyring"
+
+[litellm.gateways.production]
+base_url = "https://litellm.example.com/v1"
+model = "gpt-5"
+
+[litellm.gateways.local]
+base_url = "http://localhost:4000/v1"
+api_key_env = "LOCAL_LITELLM_API_KEY"
+"#,
+        )
+        .expect("write config");
+
+        let config = GatewaysConfig::from_config_toml(&path).expect("parse");
+
+        assert_eq!(config.default_gateway.as_deref(), Some("production"));
+        assert_eq!(
+            config.gateways.keys().collect::<Vec<_>>(),
+            vec!["local", "production"]
+        );
+        assert_eq!(
+            config.get("local"),
+            Some(&GatewayConfig {
+                base_url: Some("http://localhost:4000/v1".to_string()),
+                api_key_env: Some("LOCAL_LITELLM_API_KEY".to_string()),
+                model: None,
+            })
+        );
+        assert_eq!(
+            config.gateways["production"].api_key_env_var("production"),
+            "LITELLM_API_KEY_PRODUCTION"
+        );
+        assert!(validate_gateway_name("staging-eu_2").is_ok());
+        assert!(validate_gateway_name("Staging EU").is_err());
+        assert_eq!(config.selected(Some("local")).as_deref(), Some("local"));
+    }
+
+    #[test]
+    fn setting_the_default_gateway_keeps_the_rest_of_the_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"# my settings
+model = "gpt-5"
+
+[litellm.gateways.local]
+base_url = "http://localhost:4000/v1"
+"#,
+        )
+        .expect("write config");
+
+        set_default_gateway(&path, "local").expect("set gateway");
+
+        assert_eq!(
+            std::fs::read_to_string(&path).expect("read config"),
+            r#"# my settings
+model = "gpt-5"
+
+[litellm]
+gateway = "local"
+
+[litellm.gateways.local]
+base_url = "http://localhost:4000/v1"
+"#
+        );
+        assert_eq!(
+            GatewaysConfig::from_config_toml(&path)
+                .expect("parse")
+                .default_gateway
+                .as_deref(),
+            Some("local")
+        );
+        assert!(set_default_gateway(&path, "Not A Name").is_err());
+    }
+}
@@ -0,0 +1,20 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+//! LiteLLM gateways outside a Codex session: the named gateways configured
+//! under `[litellm.gateways]`, checking a base URL and API key during
+//! first-run setup, listing a gateway's models, and picking a default model
+//! from that list in a plain terminal.
+
+mod gateways;
+mod picker;
+mod probe;
+
+pub use gateways::{
+    gateway_provider_name, set_default_gateway, validate_gateway_name, GatewayConfig,
+    GatewaysConfig, GATEWAY_ENV,
+};
+pub use picker::pick_model;
+pub use probe::{fetch_gateway_models, probe_gateway, GatewayProbe};
@@ -0,0 +1,148 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use std::io::{self, BufRead, Write};
+
+/// Rows shown at once; longer lists ask the user to narrow them down.
+const PAGE_SIZE: usize = 20;
+
+/// Lets the user pick one of `models` in a plain terminal: a numbered list
+/// that typing text filters. Only listed slugs can be chosen. Returns `None`
+/// when there is nothing to pick, the user presses Enter without a filter,
+/// or input ends.
+pub fn pick_model(models: &[String]) -> io::Result<Option<String>> {
+    pick_model_with(models, &mut io::stdin().lock(), &mut io::stderr())
+}
+
+fn pick_model_with(
+    models: &[String],
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> io::Result<Option<String>> {
+    let mut sorted: Vec<&str> = models.iter().map(String::as_str).collect();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if sorted.is_empty() {
+        return Ok(None);
+    }
+    let mut filter = String::new();
+
+    loop {
+        let matches: Vec<&str> = sorted
+            .iter()
+            .copied()
+            .filter(|model| matches_filter(model, &filter))
+            .collect();
+        if matches.is_empty() {
+            writeln!(output, "No models match `{filter}`.")?;
+            filter.clear();
+            continue;
+        }
+        if filter.is_empty() {
+            writeln!(output, "Models available ({}):", matches.len())?;
+        } else {
+            writeln!(output, "Models matching `{filter}` ({}):", matches.len())?;
+        }
+        for (index, model) in matches.iter().take(PAGE_SIZE).enumerate() {
+            writeln!(output, "  {:>3}. {model}", index + 1)?;
+        }
+        if matches.len() > PAGE_SIZE {
+            writeln!(
+                output,
+                "  ... {} more; type part of a name to narrow the list.",
+                matches.len() - PAGE_SIZE
+            )?;
+        }
+        if filter.is_empty() {
+            write!(
+                output,
+                "Default model: number, text to filter, or Enter to choose later with /model: "
+            )?;
+        } else {
+            write!(
+                output,
+                "Default model: number, text to filter, or Enter to show all models: "
+            )?;
+        }
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let answer = line.trim();
+        if answer.is_empty() {
+            if filter.is_empty() {
+                return Ok(None);
+            }
+            filter.clear();
+            continue;
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=matches.len().min(PAGE_SIZE)).contains(&number) => {
+                return Ok(Some(matches[number - 1].to_string()));
+            }
+            Ok(_) => writeln!(output, "Pick a number from the list.")?,
+            Err(_) => filter = answer.to_string(),
+        }
+    }
+}
+
+/// Case-insensitive; every whitespace-separated term must appear.
+fn matches_filter(model: &str, filter: &str) -> bool {
+    let model = model.to_ascii_lowercase();
+    filter
+        .split_whitespace()
+        .all(|term| model.contains(&term.to_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn models() -> Vec<String> {
+        [
+            "vercel/maa/minimax-m2.7-highspeed",
+            "gpt-5",
+            "claude-sonnet-4",
+            "gpt-5-mini",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn pick(answers: &str) -> (Option<String>, String) {
+        let mut output = Vec::new();
+        let picked =
+            pick_model_with(&models(), &mut io::Cursor::new(answers), &mut output).expect("pick");
+        (picked, String::from_utf8(output).expect("utf8"))
+    }
+
+    #[test]
+    fn picks_by_number_from_the_sorted_or_filtered_list() {
+        let (picked, output) = pick("2\n");
+        assert_eq!(picked.as_deref(), Some("gpt-5"));
+        assert!(output.contains("    1. claude-sonnet-4\n    2. gpt-5\n"));
+
+        // A typo matches nothing; two terms narrow the list to one model.
+        let (picked, output) = pick("gtp\nGPT mini\n1\n");
+        assert_eq!(picked.as_deref(), Some("gpt-5-mini"));
+        assert!(output.contains("No models match `gtp`."));
+        assert!(output.contains("Models matching `GPT mini` (1):\n    1. gpt-5-mini\n"));
+
+        let (picked, output) = pick("9\n\n");
+        assert_eq!(picked, None);
+        assert!(output.contains("Pick a number from the list."));
+    }
+
+    #[test]
+    fn an_empty_list_picks_nothing_without_prompting() {
+        let mut output = Vec::new();
+        let picked = pick_model_with(&[], &mut io::Cursor::new("1\n"), &mut output).expect("pick");
+        assert_eq!(picked, None);
+        assert!(output.is_empty());
+    }
+}
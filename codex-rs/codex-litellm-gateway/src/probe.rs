@@ -0,0 +1,356 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+use url::Url;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What `probe_gateway` found out about a base URL and API key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GatewayProbe {
+    /// `{base_url}/models` answered with a model list for this key.
+    Ready {
+        models: Vec<String>,
+    },
+    InvalidUrl {
+        base_url: String,
+        detail: String,
+    },
+    /// DNS lookup, connect or read failed.
+    Unreachable {
+        host: String,
+        detail: String,
+    },
+    /// The TLS handshake failed, e.g. an untrusted certificate.
+    Tls {
+        host: String,
+        detail: String,
+    },
+    /// HTTP 401 or 403: the key is wrong, expired or lacks access.
+    Unauthorized {
+        status: u16,
+    },
+    /// `{base_url}/models` failed but `{suggested_base_url}/models` works.
+    MissingV1 {
+        suggested_base_url: String,
+    },
+    /// `/models` failed with another status. `gateway_alive` records whether
+    /// LiteLLM's `/health/liveliness` answered on the same host.
+    UnexpectedStatus {
+        status: u16,
+        gateway_alive: bool,
+    },
+    /// A 2xx answer that is not an OpenAI-style model list, e.g. an HTML page.
+    NotAModelList,
+}
+
+impl GatewayProbe {
+    pub fn is_ready(&self) -> bool {
+        matches!(self, Self::Ready { .. })
+    }
+
+    /// Whether re-entering the API key, rather than the base URL, is the
+    /// likely fix.
+    pub fn blames_api_key(&self) -> bool {
+        matches!(self, Self::Unauthorized { .. })
+    }
+}
+
+impl fmt::Display for GatewayProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ready { models } if models.is_empty() => {
+                write!(f, "Connected, but the gateway lists no models for this key.")
+            }
+            Self::Ready { models } => write!(f, "Connected: {} models available.", models.len()),
+            Self::InvalidUrl { base_url, detail } => {
+                write!(f, "`{base_url}` is not a valid URL: {detail}.")
+            }
+            Self::Unreachable { host, detail } => write!(f, "Could not reach {host}: {detail}."),
+            Self::Tls { host, detail } => write!(
+                f,
+                "TLS handshake with {host} failed: {detail}. Check the gateway certificate, or use http:// for a local proxy."
+            ),
+            Self::Unauthorized { status } => {
+                write!(f, "The gateway rejected the API key (HTTP {status}).")
+            }
+            Self::MissingV1 { suggested_base_url } => write!(
+                f,
+                "No model list at this base URL, but there is one at {suggested_base_url}. The base URL is probably missing `/v1`."
+            ),
+            Self::UnexpectedStatus {
+                status,
+                gateway_alive: true,
+            } => write!(
+                f,
+                "The model list returned HTTP {status}, although LiteLLM's health check on the same host passes. Check the path of the base URL."
+            ),
+            Self::UnexpectedStatus {
+                status,
+                gateway_alive: false,
+            } => write!(f, "The model list returned HTTP {status}."),
+            Self::NotAModelList => write!(
+                f,
+                "The base URL answered, but not with an OpenAI-style model list. Is it the LiteLLM API rather than its admin UI?"
+            ),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// Checks a base URL and API key against the gateway with
+/// `GET {base_url}/models`. When that fails, also tries `{base_url}/v1` and
+/// LiteLLM's unauthenticated `/health/liveliness` to say why. Pass Codex's
+/// own HTTP client so proxies and custom CA certificates apply here too.
+pub async fn probe_gateway(
+    client: &reqwest::Client,
+    base_url: &str,
+    api_key: &str,
+) -> GatewayProbe {
+    let base_url = base_url.trim().trim_end_matches('/');
+    let parsed = match Url::parse(base_url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host_str().is_some() => url,
+        Ok(url) => {
+            return GatewayProbe::InvalidUrl {
+                base_url: base_url.to_string(),
+                detail: format!(
+                    "expected an http:// or https:// URL, got `{}:`",
+                    url.scheme()
+                ),
+            }
+        }
+        Err(err) => {
+            return GatewayProbe::InvalidUrl {
+                base_url: base_url.to_string(),
+                detail: err.to_string(),
+            }
+        }
+    };
+
+    let probe = fetch_models(client, base_url, api_key, &parsed).await;
+    let status = match probe {
+        GatewayProbe::UnexpectedStatus { status, .. } => Some(status),
+        GatewayProbe::NotAModelList => None,
+        other => return other,
+    };
+
+    if !parsed.path().trim_end_matches('/').ends_with("/v1") {
+        let suggested_base_url = format!("{base_url}/v1");
+        if fetch_models(client, &suggested_base_url, api_key, &parsed)
+            .await
+            .is_ready()
+        {
+            return GatewayProbe::MissingV1 { suggested_base_url };
+        }
+    }
+    match status {
+        Some(status) => GatewayProbe::UnexpectedStatus {
+            status,
+            gateway_alive: liveliness(client, &parsed).await,
+        },
+        None => GatewayProbe::NotAModelList,
+    }
+}
+
+/// Lists the model ids the gateway offers to this key, in gateway order.
+pub async fn fetch_gateway_models(
+    client: &reqwest::Client,
+    base_url: &str,
+    api_key: &str,
+) -> Result<Vec<String>, GatewayProbe> {
+    match probe_gateway(client, base_url, api_key).await {
+        GatewayProbe::Ready { models } => Ok(models),
+        other => Err(other),
+    }
+}
+
+async fn fetch_models(
+    client: &reqwest::Client,
+    base_url: &str,
+    api_key: &str,
+    parsed: &Url,
+) -> GatewayProbe {
+    let response = client
+        .get(format!("{base_url}/models"))
+        .bearer_auth(api_key)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await;
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => return classify_error(&err, parsed),
+    };
+    match response.status().as_u16() {
+        status @ (401 | 403) => GatewayProbe::Unauthorized { status },
+        status if !response.status().is_success() => GatewayProbe::UnexpectedStatus {
+            status,
+            gateway_alive: false,
+        },
+        _ => match response.json::<ModelList>().await {
+            Ok(list) => GatewayProbe::Ready {
+                models: list.data.into_iter().map(|model| model.id).collect(),
+            },
+            Err(_) => GatewayProbe::NotAModelList,
+        },
+    }
+}
+
+fn classify_error(err: &reqwest::Error, parsed: &Url) -> GatewayProbe {
+    let host = match parsed.port() {
+        Some(port) => format!("{}:{port}", parsed.host_str().unwrap_or_default()),
+        None => parsed.host_str().unwrap_or_default().to_string(),
+    };
+    let detail = error_detail(err);
+    if err.is_builder() {
+        return GatewayProbe::InvalidUrl {
+            base_url: parsed.to_string(),
+            detail,
+        };
+    }
+    // reqwest has no TLS error kind; the TLS backends name certificates or
+    // the handshake somewhere in the cause chain.
+    let lower = detail.to_ascii_lowercase();
+    if ["certificate", "tls", "ssl", "handshake"]
+        .iter()
+        .any(|needle| lower.contains(needle))
+    {
+        GatewayProbe::Tls { host, detail }
+    } else {
+        GatewayProbe::Unreachable { host, detail }
+    }
+}
+
+/// The innermost cause, which names the actual problem ("connection refused",
+/// "invalid peer certificate: UnknownIssuer").
+fn error_detail(err: &reqwest::Error) -> String {
+    let mut detail = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        detail = err.to_string();
+        source = err.source();
+    }
+    detail
+}
+
+/// Whether `GET /health/liveliness` on the gateway's origin answers 2xx.
+async fn liveliness(client: &reqwest::Client, parsed: &Url) -> bool {
+    let mut url = parsed.clone();
+    url.set_path("/health/liveliness");
+    url.set_query(None);
+    client
+        .get(url)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .is_ok_and(|response| response.status().is_success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serves canned answers by request path until the test process exits.
+    fn serve(routes: &'static [(&'static str, u16, &'static str)]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().expect("clone"));
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).expect("request line");
+                let mut authorized = false;
+                loop {
+                    let mut header = String::new();
+                    if reader.read_line(&mut header).unwrap_or(0) == 0 || header == "\r\n" {
+                        break;
+                    }
+                    authorized |= header.eq_ignore_ascii_case("authorization: bearer sk-good\r\n");
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let (status, body) = match routes.iter().find(|(route, ..)| *route == path) {
+                    Some((route, ..)) if route.ends_with("/models") && !authorized => (401, "{}"),
+                    Some((_, status, body)) => (*status, *body),
+                    None => (404, "not found"),
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    const MODELS: &str = r#"{"object":"list","data":[{"id":"gpt-5"},{"id":"claude-sonnet-4"}]}"#;
+
+    #[tokio::test]
+    async fn reports_models_bad_keys_and_a_missing_v1() {
+        let client = reqwest::Client::new();
+        let gateway = serve(&[
+            ("/v1/models", 200, MODELS),
+            ("/health/liveliness", 200, "\"I'm alive!\""),
+        ]);
+
+        assert_eq!(
+            probe_gateway(&client, &format!("{gateway}/v1/"), "sk-good").await,
+            GatewayProbe::Ready {
+                models: vec!["gpt-5".to_string(), "claude-sonnet-4".to_string()]
+            }
+        );
+        assert_eq!(
+            probe_gateway(&client, &format!("{gateway}/v1"), "sk-typo").await,
+            GatewayProbe::Unauthorized { status: 401 }
+        );
+        assert_eq!(
+            probe_gateway(&client, &gateway, "sk-good").await,
+            GatewayProbe::MissingV1 {
+                suggested_base_url: format!("{gateway}/v1")
+            }
+        );
+        assert_eq!(
+            probe_gateway(&client, &format!("{gateway}/api/v1"), "sk-good").await,
+            GatewayProbe::UnexpectedStatus {
+                status: 404,
+                gateway_alive: true
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_hosts_and_bad_urls() {
+        let client = reqwest::Client::new();
+        let port = TcpListener::bind("127.0.0.1:0")
+            .expect("bind")
+            .local_addr()
+            .expect("addr")
+            .port();
+
+        match probe_gateway(&client, &format!("http://127.0.0.1:{port}/v1"), "sk-good").await {
+            GatewayProbe::Unreachable { host, .. } => assert_eq!(host, format!("127.0.0.1:{port}")),
+            other => panic!("expected unreachable, got {other:?}"),
+        }
+        assert!(matches!(
+            probe_gateway(&client, "litellm.example.com/v1", "sk-good").await,
+            GatewayProbe::InvalidUrl { .. }
+        ));
+    }
+}
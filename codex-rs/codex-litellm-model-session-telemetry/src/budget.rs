@@ -0,0 +1,428 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::config::read_config_table;
+
+/// Soft and hard ceilings for one budget scope. Soft limits only warn; hard
+/// limits refuse the next turn.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct BudgetLimits {
+    #[serde(default)]
+    pub soft_tokens: Option<i64>,
+    #[serde(default)]
+    pub hard_tokens: Option<i64>,
+    #[serde(default)]
+    pub soft_cost_usd: Option<f64>,
+    #[serde(default)]
+    pub hard_cost_usd: Option<f64>,
+}
+
+/// The `[litellm.budget]` table from `config.toml`:
+///
+/// ```toml
+/// [litellm.budget.session]
+/// soft_tokens = 2_000_000
+/// hard_cost_usd = 10.0
+///
+/// [litellm.budget.daily]
+/// hard_cost_usd = 25.0
+///
+/// [litellm.budget.models."vercel/minimax-m2.7"]
+/// soft_cost_usd = 2.0
+/// ```
+///
+/// Model limits apply to that model's usage within the current session.
+/// Daily limits cover every session recorded in the telemetry log for the
+/// current UTC day.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct BudgetConfig {
+    #[serde(default)]
+    pub session: BudgetLimits,
+    #[serde(default)]
+    pub daily: BudgetLimits,
+    #[serde(default)]
+    pub models: BTreeMap<String, BudgetLimits>,
+}
+
+impl BudgetConfig {
+    /// Reads `[litellm.budget]` from a `config.toml`. A missing file or table
+    /// means no budget.
+    pub fn from_config_toml(path: &Path) -> io::Result<Option<Self>> {
+        read_config_table(path, &["litellm", "budget"])
+    }
+
+    pub(crate) fn evaluate(&self, model: &str, usage: &BudgetUsage) -> BudgetStatus {
+        let mut breaches = Vec::new();
+        check_scope(
+            BudgetScope::Session,
+            &self.session,
+            usage.session_tokens,
+            usage.session_cost_usd,
+            &mut breaches,
+        );
+        if let Some(limits) = self.models.get(model) {
+            check_scope(
+                BudgetScope::Model(model.to_string()),
+                limits,
+                usage.model_tokens,
+                usage.model_cost_usd,
+                &mut breaches,
+            );
+        }
+        check_scope(
+            BudgetScope::Daily,
+            &self.daily,
+            usage.daily_tokens,
+            usage.daily_cost_usd,
+            &mut breaches,
+        );
+
+        if breaches.is_empty() {
+            BudgetStatus::WithinBudget
+        } else if breaches.iter().any(|breach| breach.hard) {
+            breaches.retain(|breach| breach.hard);
+            BudgetStatus::HardLimitExceeded(breaches)
+        } else {
+            BudgetStatus::SoftLimitExceeded(breaches)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BudgetUsage {
+    pub(crate) session_tokens: i64,
+    pub(crate) session_cost_usd: f64,
+    pub(crate) model_tokens: i64,
+    pub(crate) model_cost_usd: f64,
+    pub(crate) daily_tokens: i64,
+    pub(crate) daily_cost_usd: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BudgetScope {
+    Session,
+    Model(String),
+    Daily,
+}
+
+impl fmt::Display for BudgetScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetScope::Session => f.write_str("session"),
+            BudgetScope::Model(model) => write!(f, "model {model}"),
+            BudgetScope::Daily => f.write_str("daily"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetMetric {
+    Tokens,
+    CostUsd,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetBreach {
+    pub scope: BudgetScope,
+    pub metric: BudgetMetric,
+    pub hard: bool,
+    pub used: f64,
+    pub limit: f64,
+}
+
+impl fmt::Display for BudgetBreach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.hard { "hard" } else { "soft" };
+        match self.metric {
+            BudgetMetric::Tokens => write!(
+                f,
+                "{} {kind} token budget reached: {} of {} tokens used",
+                self.scope, self.used as i64, self.limit as i64
+            ),
+            BudgetMetric::CostUsd => write!(
+                f,
+                "{} {kind} cost budget reached: ${:.2} of ${:.2} used",
+                self.scope, self.used, self.limit
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetStatus {
+    WithinBudget,
+    /// The turn may proceed, but the UI should warn.
+    SoftLimitExceeded(Vec<BudgetBreach>),
+    /// The turn must not be sent to the gateway.
+    HardLimitExceeded(Vec<BudgetBreach>),
+}
+
+impl BudgetStatus {
+    pub fn breaches(&self) -> &[BudgetBreach] {
+        match self {
+            BudgetStatus::WithinBudget => &[],
+            BudgetStatus::SoftLimitExceeded(breaches)
+            | BudgetStatus::HardLimitExceeded(breaches) => breaches,
+        }
+    }
+
+    /// Turns a hard breach into an error suitable for refusing the turn: an
+    /// `io::ErrorKind::QuotaExceeded` error wrapping `BudgetExceeded`.
+    pub fn into_result(self) -> io::Result<Self> {
+        match self {
+            BudgetStatus::HardLimitExceeded(breaches) => Err(io::Error::new(
+                io::ErrorKind::QuotaExceeded,
+                BudgetExceeded { breaches },
+            )),
+            status => Ok(status),
+        }
+    }
+}
+
+/// The hard limits that refused a turn. Callers can recover it from the
+/// error returned by `BudgetStatus::into_result` with `get_ref` and
+/// `downcast_ref`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetExceeded {
+    pub breaches: Vec<BudgetBreach>,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let details = self
+            .breaches
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        write!(
+            f,
+            "LiteLLM budget exceeded, turn not sent: {details}. Raise the limit under [litellm.budget] in config.toml to continue."
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+fn check_scope(
+    scope: BudgetScope,
+    limits: &BudgetLimits,
+    tokens: i64,
+    cost_usd: f64,
+    breaches: &mut Vec<BudgetBreach>,
+) {
+    let checks = [
+        (
+            BudgetMetric::Tokens,
+            true,
+            limits.hard_tokens.map(|v| v as f64),
+            tokens as f64,
+        ),
+        (
+            BudgetMetric::Tokens,
+            false,
+            limits.soft_tokens.map(|v| v as f64),
+            tokens as f64,
+        ),
+        (BudgetMetric::CostUsd, true, limits.hard_cost_usd, cost_usd),
+        (BudgetMetric::CostUsd, false, limits.soft_cost_usd, cost_usd),
+    ];
+    for (metric, hard, limit, used) in checks {
+        let Some(limit) = limit else {
+            continue;
+        };
+        if used < limit {
+            continue;
+        }
+        // A hard breach already covers the matching soft limit.
+        if !hard
+            && breaches
+                .iter()
+                .any(|breach| breach.hard && breach.scope == scope && breach.metric == metric)
+        {
+            continue;
+        }
+        breaches.push(BudgetBreach {
+            scope: scope.clone(),
+            metric,
+            hard,
+            used,
+            limit,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn limits(soft_tokens: i64, hard_tokens: i64) -> BudgetLimits {
+        BudgetLimits {
+            soft_tokens: Some(soft_tokens),
+            hard_tokens: Some(hard_tokens),
+            soft_cost_usd: Some(1.0),
+            hard_cost_usd: Some(2.0),
+        }
+    }
+
+    fn breach(
+        scope: BudgetScope,
+        metric: BudgetMetric,
+        hard: bool,
+        used: f64,
+        limit: f64,
+    ) -> BudgetBreach {
+        BudgetBreach {
+            scope,
+            metric,
+            hard,
+            used,
+            limit,
+        }
+    }
+
+    #[test]
+    fn session_limits_warn_at_the_soft_threshold_and_refuse_at_the_hard_one() {
+        let config = BudgetConfig {
+            session: limits(100, 200),
+            ..BudgetConfig::default()
+        };
+        let at = |session_tokens, session_cost_usd| {
+            config.evaluate(
+                "gpt-5",
+                &BudgetUsage {
+                    session_tokens,
+                    session_cost_usd,
+                    ..BudgetUsage::default()
+                },
+            )
+        };
+
+        assert_eq!(at(99, 0.99), BudgetStatus::WithinBudget);
+        assert_eq!(
+            at(100, 0.5),
+            BudgetStatus::SoftLimitExceeded(vec![breach(
+                BudgetScope::Session,
+                BudgetMetric::Tokens,
+                false,
+                100.0,
+                100.0,
+            )])
+        );
+        // Only hard breaches are reported once any limit is hard.
+        assert_eq!(
+            at(200, 1.5),
+            BudgetStatus::HardLimitExceeded(vec![breach(
+                BudgetScope::Session,
+                BudgetMetric::Tokens,
+                true,
+                200.0,
+                200.0,
+            )])
+        );
+        assert_eq!(
+            at(150, 2.0),
+            BudgetStatus::HardLimitExceeded(vec![breach(
+                BudgetScope::Session,
+                BudgetMetric::CostUsd,
+                true,
+                2.0,
+                2.0,
+            )])
+        );
+    }
+
+    #[test]
+    fn model_limits_apply_only_to_their_model_and_daily_limits_to_every_model() {
+        let config = BudgetConfig {
+            daily: BudgetLimits {
+                hard_tokens: Some(1_000),
+                ..BudgetLimits::default()
+            },
+            models: BTreeMap::from([("gpt-5".to_string(), limits(10, 20))]),
+            ..BudgetConfig::default()
+        };
+        let usage = BudgetUsage {
+            model_tokens: 15,
+            daily_tokens: 500,
+            ..BudgetUsage::default()
+        };
+
+        assert_eq!(
+            config.evaluate("gpt-5", &usage).breaches(),
+            [breach(
+                BudgetScope::Model("gpt-5".to_string()),
+                BudgetMetric::Tokens,
+                false,
+                15.0,
+                10.0,
+            )]
+        );
+        assert_eq!(
+            config.evaluate("claude-sonnet-4", &usage),
+            BudgetStatus::WithinBudget
+        );
+        assert_eq!(
+            config.evaluate(
+                "claude-sonnet-4",
+                &BudgetUsage {
+                    daily_tokens: 1_000,
+                    ..usage
+                },
+            ),
+            BudgetStatus::HardLimitExceeded(vec![breach(
+                BudgetScope::Daily,
+                BudgetMetric::Tokens,
+                true,
+                1_000.0,
+                1_000.0,
+            )])
+        );
+    }
+
+    #[test]
+    fn only_hard_breaches_become_errors() {
+        let soft = BudgetStatus::SoftLimitExceeded(vec![breach(
+            BudgetScope::Session,
+            BudgetMetric::Tokens,
+            false,
+            100.0,
+            100.0,
+        )]);
+        assert_eq!(
+            BudgetStatus::WithinBudget.into_result().ok(),
+            Some(BudgetStatus::WithinBudget)
+        );
+        assert_eq!(soft.clone().into_result().ok(), Some(soft));
+
+        let breaches = vec![breach(
+            BudgetScope::Daily,
+            BudgetMetric::CostUsd,
+            true,
+            25.5,
+            25.0,
+        )];
+        let err = BudgetStatus::HardLimitExceeded(breaches.clone())
+            .into_result()
+            .expect_err("hard limit");
+        assert_eq!(err.kind(), io::ErrorKind::QuotaExceeded);
+        assert_eq!(
+            err.get_ref()
+                .and_then(|inner| inner.downcast_ref::<BudgetExceeded>()),
+            Some(&BudgetExceeded { breaches })
+        );
+        assert_eq!(
+            err.to_string(),
+            "LiteLLM budget exceeded, turn not sent: daily hard cost budget reached: $25.50 of $25.00 used. Raise the limit under [litellm.budget] in config.toml to continue."
+        );
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use serde::de::DeserializeOwned;
+use std::io;
+use std::path::Path;
+
+/// Reads the table at `keys` (e.g. `["telemetry", "rotation"]`) from a
+/// `config.toml`. A missing file or table yields `None`.
+pub(crate) fn read_config_table<T: DeserializeOwned>(
+    path: &Path,
+    keys: &[&str],
+) -> io::Result<Option<T>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let root: toml::Value =
+        toml::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut table = &root;
+    for key in keys {
+        match table.get(key) {
+            Some(value) => table = value,
+            None => return Ok(None),
+        }
+    }
+    table
+        .clone()
+        .try_into()
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
@@ -0,0 +1,404 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::query::{in_window, parse_bound};
+use crate::record::{read_log_records, LogRecord};
+use crate::{normalize_session_id, TurnRecord};
+
+/// Columns of every export, in order. New columns are only ever appended so
+/// downstream spreadsheets and queries keep working.
+pub const EXPORT_COLUMNS: [&str; 14] = [
+    "ts",
+    "session_id",
+    "model",
+    "effort",
+    "prompt_tokens",
+    "completion_tokens",
+    "reasoning_tokens",
+    "cached_tokens",
+    "cache_creation_tokens",
+    "total_tokens",
+    "cost_usd",
+    "repo",
+    "branch",
+    "cwd",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// Needs the `parquet` feature.
+    Parquet,
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        })
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "parquet" => Ok(ExportFormat::Parquet),
+            other => Err(format!(
+                "unknown export format `{other}`; expected csv or parquet"
+            )),
+        }
+    }
+}
+
+/// Which turns to export. `since` is inclusive and `until` exclusive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl ExportQuery {
+    /// Same bounds as `UsageQuery::from_bounds`: an RFC 3339 timestamp or a
+    /// `YYYY-MM-DD` UTC date, with a date given as `until` covering that day.
+    pub fn from_bounds(since: Option<&str>, until: Option<&str>) -> io::Result<Self> {
+        Ok(Self {
+            since: since.map(|value| parse_bound(value, false)).transpose()?,
+            until: until.map(|value| parse_bound(value, true)).transpose()?,
+        })
+    }
+}
+
+/// Writes one row per turn in the telemetry log at `path` (including rotated
+/// segments) to `out`, in log order. Returns the number of rows written.
+/// Windows holding aggregate-mode rollups are refused, since those have no
+/// per-turn rows to export.
+pub fn export_turns(
+    path: &Path,
+    query: &ExportQuery,
+    format: ExportFormat,
+    out: impl Write + Send,
+) -> io::Result<usize> {
+    let mut turns = Vec::new();
+    let mut rollups = 0;
+    for record in read_log_records(path, |record| {
+        in_window(record.ts(), query.since, query.until)
+    })? {
+        match record {
+            LogRecord::Turn(turn) => turns.push(*turn),
+            LogRecord::Rollup(_) => rollups += 1,
+            LogRecord::Outcome(_) => {}
+        }
+    }
+    if rollups > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the telemetry log has {rollups} aggregate rollup lines in this window, written \
+                 under `[telemetry.privacy] mode = \"aggregate\"`; they have no per-turn rows. \
+                 Use `codex-litellm usage --by day` or `--by model` for them, or narrow \
+                 --since/--until to turns logged before aggregate mode was enabled"
+            ),
+        ));
+    }
+    match format {
+        ExportFormat::Csv => write_csv(&turns, out)?,
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => parquet_writer::write_parquet(&turns, out)?,
+        #[cfg(not(feature = "parquet"))]
+        ExportFormat::Parquet => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Parquet export needs the telemetry crate's `parquet` feature",
+            ));
+        }
+    }
+    Ok(turns.len())
+}
+
+fn format_ts(ts: DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn write_csv(turns: &[TurnRecord], out: impl Write) -> io::Result<()> {
+    let mut out = io::BufWriter::new(out);
+    writeln!(out, "{}", EXPORT_COLUMNS.join(","))?;
+    for turn in turns {
+        let usage = turn.usage();
+        let cells = [
+            format_ts(turn.ts),
+            normalize_session_id(turn.session_id.as_deref()),
+            turn.model.clone(),
+            turn.reasoning_effort.clone().unwrap_or_default(),
+            usage.prompt_tokens.to_string(),
+            usage.completion_tokens.to_string(),
+            usage.reasoning_tokens.to_string(),
+            usage.cached_tokens.to_string(),
+            usage.cache_creation_tokens.to_string(),
+            usage.total_tokens.to_string(),
+            turn.cost_usd
+                .map(|cost| cost.to_string())
+                .unwrap_or_default(),
+            turn.repo.clone().unwrap_or_default(),
+            turn.branch.clone().unwrap_or_default(),
+            turn.cwd
+                .as_ref()
+                .map(|cwd| cwd.display().to_string())
+                .unwrap_or_default(),
+        ];
+        let row: Vec<String> = cells.iter().map(|cell| csv_cell(cell)).collect();
+        writeln!(out, "{}", row.join(","))?;
+    }
+    out.flush()
+}
+
+/// Quotes a cell per RFC 4180 when it contains a delimiter, quote or newline.
+fn csv_cell(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_writer {
+    use parquet::basic::Compression;
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::io::{self, Write};
+    use std::sync::Arc;
+
+    use crate::{normalize_session_id, TurnRecord};
+
+    /// Parquet schema for `EXPORT_COLUMNS`, in the same order.
+    const SCHEMA: &str = "message codex_litellm_turn {
+        REQUIRED INT64 ts (TIMESTAMP(MILLIS, true));
+        REQUIRED BYTE_ARRAY session_id (UTF8);
+        REQUIRED BYTE_ARRAY model (UTF8);
+        OPTIONAL BYTE_ARRAY effort (UTF8);
+        REQUIRED INT64 prompt_tokens;
+        REQUIRED INT64 completion_tokens;
+        REQUIRED INT64 reasoning_tokens;
+        REQUIRED INT64 cached_tokens;
+        REQUIRED INT64 cache_creation_tokens;
+        REQUIRED INT64 total_tokens;
+        OPTIONAL DOUBLE cost_usd;
+        OPTIONAL BYTE_ARRAY repo (UTF8);
+        OPTIONAL BYTE_ARRAY branch (UTF8);
+        OPTIONAL BYTE_ARRAY cwd (UTF8);
+    }";
+
+    /// Non-null values plus the definition level of every row.
+    fn optional<T>(
+        turns: &[TurnRecord],
+        value: impl Fn(&TurnRecord) -> Option<T>,
+    ) -> (Vec<T>, Vec<i16>) {
+        let mut values = Vec::new();
+        let mut levels = Vec::with_capacity(turns.len());
+        for turn in turns {
+            match value(turn) {
+                Some(found) => {
+                    values.push(found);
+                    levels.push(1);
+                }
+                None => levels.push(0),
+            }
+        }
+        (values, levels)
+    }
+
+    pub(super) fn write_parquet(turns: &[TurnRecord], out: impl Write + Send) -> io::Result<()> {
+        write(turns, out).map_err(io::Error::other)
+    }
+
+    fn write(turns: &[TurnRecord], out: impl Write + Send) -> parquet::errors::Result<()> {
+        let schema = Arc::new(parse_message_type(SCHEMA)?);
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = SerializedFileWriter::new(out, schema, Arc::new(properties))?;
+        let mut row_group = writer.next_row_group()?;
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            match index {
+                0 => {
+                    let values: Vec<i64> = turns
+                        .iter()
+                        .map(|turn| turn.ts.timestamp_millis())
+                        .collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                1 | 2 => {
+                    let values: Vec<ByteArray> = turns
+                        .iter()
+                        .map(|turn| match index {
+                            1 => normalize_session_id(turn.session_id.as_deref()),
+                            _ => turn.model.clone(),
+                        })
+                        .map(|value| ByteArray::from(value.as_str()))
+                        .collect();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, None, None)?;
+                }
+                3 => {
+                    let (values, levels) = optional(turns, |turn| {
+                        turn.reasoning_effort.as_deref().map(ByteArray::from)
+                    });
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, Some(&levels), None)?;
+                }
+                4..=9 => {
+                    let values: Vec<i64> = turns
+                        .iter()
+                        .map(|turn| {
+                            let usage = turn.usage();
+                            match index {
+                                4 => usage.prompt_tokens,
+                                5 => usage.completion_tokens,
+                                6 => usage.reasoning_tokens,
+                                7 => usage.cached_tokens,
+                                8 => usage.cache_creation_tokens,
+                                _ => usage.total_tokens,
+                            }
+                        })
+                        .collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                10 => {
+                    let (values, levels) = optional(turns, |turn| turn.cost_usd);
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&values, Some(&levels), None)?;
+                }
+                _ => {
+                    let (values, levels) = optional(turns, |turn| {
+                        let value = match index {
+                            11 => turn.repo.clone(),
+                            12 => turn.branch.clone(),
+                            _ => turn.cwd.as_ref().map(|cwd| cwd.display().to_string()),
+                        };
+                        value.map(|value| ByteArray::from(value.as_str()))
+                    });
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, Some(&levels), None)?;
+                }
+            }
+            column.close()?;
+            index += 1;
+        }
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const LOG: &str = r#"{"ts":"2025-03-01T10:00:00.000Z","session_id":"a","model":"m1","reasoning_effort":"high","cwd":"/src/widgets","repo":"acme/widgets","branch":"main","prompt_tokens":100,"completion_tokens":10,"total_tokens":110,"cost_usd":0.5}
+{"event":"request_outcome","ts":"2025-03-01T11:00:00.000Z","session_id":"a","model":"m1","error_class":"timeout"}
+{"ts":"2025-03-02T08:00:00.000Z","model":"team,\"x\"","prompt_tokens":300,"completion_tokens":30,"total_tokens":330}
+{"ts":"2025-04-01T08:00:00.000Z","session_id":"b","model":"m1","total_tokens":1}
+"#;
+
+    fn write_log() -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        std::fs::write(&path, LOG).expect("write log");
+        (dir, path)
+    }
+
+    #[test]
+    fn csv_has_a_stable_header_and_only_turns_in_the_window() {
+        let (_dir, path) = write_log();
+        let query =
+            ExportQuery::from_bounds(Some("2025-03-01"), Some("2025-03-31")).expect("parse bounds");
+        let mut out = Vec::new();
+
+        let rows = export_turns(&path, &query, ExportFormat::Csv, &mut out).expect("export");
+
+        assert_eq!(rows, 2);
+        assert_eq!(
+            String::from_utf8(out).expect("utf-8"),
+            "ts,session_id,model,effort,prompt_tokens,completion_tokens,reasoning_tokens,cached_tokens,cache_creation_tokens,total_tokens,cost_usd,repo,branch,cwd\n\
+             2025-03-01T10:00:00.000Z,a,m1,high,100,10,0,0,0,110,0.5,acme/widgets,main,/src/widgets\n\
+             2025-03-02T08:00:00.000Z,_default,\"team,\"\"x\"\"\",,300,30,0,0,0,330,,,,\n"
+        );
+    }
+
+    #[test]
+    fn windows_with_aggregate_rollups_are_refused() {
+        let (_dir, path) = write_log();
+        let rollup = r#"{"event":"rollup","ts":"2025-05-01T10:00:00.000Z","period_end":"2025-05-01T11:00:00.000Z","model":"m1","turns":3,"total_tokens":90}"#;
+        let mut log = std::fs::read_to_string(&path).expect("read log");
+        log.push_str(rollup);
+        log.push('\n');
+        std::fs::write(&path, log).expect("write log");
+
+        let err = export_turns(
+            &path,
+            &ExportQuery::default(),
+            ExportFormat::Csv,
+            Vec::new(),
+        )
+        .expect_err("rollups cannot be exported per turn");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("1 aggregate rollup lines"));
+
+        let before = ExportQuery::from_bounds(None, Some("2025-04-30")).expect("parse bounds");
+        let rows = export_turns(&path, &before, ExportFormat::Csv, Vec::new()).expect("export");
+        assert_eq!(rows, 3);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_columns_match_the_csv_schema() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let (dir, path) = write_log();
+        let out_path = dir.path().join("turns.parquet");
+        let out = std::fs::File::create(&out_path).expect("create parquet");
+
+        let rows = export_turns(&path, &ExportQuery::default(), ExportFormat::Parquet, out)
+            .expect("export");
+
+        let reader =
+            SerializedFileReader::new(std::fs::File::open(&out_path).expect("open parquet"))
+                .expect("read parquet");
+        let metadata = reader.metadata();
+        assert_eq!(rows, 3);
+        assert_eq!(metadata.file_metadata().num_rows(), 3);
+        let columns: Vec<String> = metadata
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect();
+        assert_eq!(columns, EXPORT_COLUMNS);
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+
+/// Per-model samples kept for percentiles. Old samples are dropped first so
+/// long sessions reflect the route's recent behavior.
+const MAX_LATENCY_SAMPLES: usize = 512;
+
+/// Wall-clock timestamps for one `/responses` request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurnTiming {
+    pub started_at: DateTime<Utc>,
+    /// When the first output delta arrived; `None` if the stream produced no
+    /// output before completing.
+    pub first_token_at: Option<DateTime<Utc>>,
+    pub completed_at: DateTime<Utc>,
+}
+
+impl TurnTiming {
+    pub fn ttft_ms(&self) -> Option<i64> {
+        self.first_token_at
+            .map(|first| (first - self.started_at).num_milliseconds().max(0))
+    }
+
+    pub fn duration_ms(&self) -> i64 {
+        (self.completed_at - self.started_at)
+            .num_milliseconds()
+            .max(0)
+    }
+
+    /// Output throughput measured from the first token when known, so queueing
+    /// and prompt processing do not count against generation speed.
+    pub fn output_tokens_per_sec(&self, completion_tokens: i64) -> Option<f64> {
+        let generation_start = self.first_token_at.unwrap_or(self.started_at);
+        let millis = (self.completed_at - generation_start).num_milliseconds();
+        (millis > 0 && completion_tokens > 0)
+            .then(|| completion_tokens as f64 * 1000.0 / millis as f64)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct LatencySamples {
+    ttft_ms: VecDeque<i64>,
+    output_tokens_per_sec: VecDeque<f64>,
+}
+
+impl LatencySamples {
+    pub(crate) fn push(&mut self, ttft_ms: Option<i64>, output_tokens_per_sec: Option<f64>) {
+        if let Some(ttft_ms) = ttft_ms {
+            push_bounded(&mut self.ttft_ms, ttft_ms);
+        }
+        if let Some(rate) = output_tokens_per_sec {
+            push_bounded(&mut self.output_tokens_per_sec, rate);
+        }
+    }
+
+    pub(crate) fn ttft_percentile_ms(&self, percentile: f64) -> Option<i64> {
+        let mut values: Vec<i64> = self.ttft_ms.iter().copied().collect();
+        values.sort_unstable();
+        nearest_rank(&values, percentile).copied()
+    }
+
+    pub(crate) fn output_tokens_per_sec_percentile(&self, percentile: f64) -> Option<f64> {
+        let mut values: Vec<f64> = self.output_tokens_per_sec.iter().copied().collect();
+        values.sort_by(f64::total_cmp);
+        nearest_rank(&values, percentile).copied()
+    }
+}
+
+fn push_bounded<T>(samples: &mut VecDeque<T>, value: T) {
+    if samples.len() == MAX_LATENCY_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(value);
+}
+
+fn nearest_rank<T>(sorted: &[T], percentile: f64) -> Option<&T> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.clamp(1, sorted.len()) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn nearest_rank_percentiles_at_the_bounds() {
+        let mut samples = LatencySamples::default();
+        assert_eq!(samples.ttft_percentile_ms(50.0), None);
+
+        samples.push(Some(70), Some(40.0));
+        assert_eq!(samples.ttft_percentile_ms(0.0), Some(70));
+        assert_eq!(samples.ttft_percentile_ms(100.0), Some(70));
+
+        for ttft_ms in [10, 40, 20, 50, 30, 60, 90, 80, 100] {
+            samples.push(Some(ttft_ms), None);
+        }
+        samples.push(None, Some(20.0));
+        samples.push(None, Some(30.0));
+        samples.push(None, Some(10.0));
+
+        // 10 samples: p50 is the 5th and p95 the 10th smallest.
+        assert_eq!(samples.ttft_percentile_ms(0.0), Some(10));
+        assert_eq!(samples.ttft_percentile_ms(50.0), Some(50));
+        assert_eq!(samples.ttft_percentile_ms(95.0), Some(100));
+        assert_eq!(samples.ttft_percentile_ms(100.0), Some(100));
+        assert_eq!(samples.output_tokens_per_sec_percentile(50.0), Some(20.0));
+        assert_eq!(samples.output_tokens_per_sec_percentile(95.0), Some(40.0));
+    }
+
+    #[test]
+    fn percentiles_cover_only_the_latest_window() {
+        let mut samples = LatencySamples::default();
+        let total = MAX_LATENCY_SAMPLES as i64 + 100;
+        for ttft_ms in 1..=total {
+            samples.push(Some(ttft_ms), Some(ttft_ms as f64));
+        }
+
+        // Samples 1..=100 have been dropped; 101..=612 remain.
+        assert_eq!(samples.ttft_ms.len(), MAX_LATENCY_SAMPLES);
+        assert_eq!(samples.ttft_percentile_ms(0.0), Some(101));
+        assert_eq!(samples.ttft_percentile_ms(50.0), Some(100 + 256));
+        assert_eq!(samples.ttft_percentile_ms(95.0), Some(100 + 487));
+        assert_eq!(samples.ttft_percentile_ms(100.0), Some(total));
+        assert_eq!(
+            samples.output_tokens_per_sec_percentile(50.0),
+            Some((100 + 256) as f64)
+        );
+    }
+
+    #[test]
+    fn timing_derives_ttft_duration_and_throughput() {
+        let started_at = DateTime::parse_from_rfc3339("2025-03-01T10:00:00Z")
+            .expect("timestamp")
+            .with_timezone(&Utc);
+        let timing = TurnTiming {
+            started_at,
+            first_token_at: Some(started_at + chrono::Duration::milliseconds(500)),
+            completed_at: started_at + chrono::Duration::milliseconds(2_500),
+        };
+        assert_eq!(timing.ttft_ms(), Some(500));
+        assert_eq!(timing.duration_ms(), 2_500);
+        assert_eq!(timing.output_tokens_per_sec(100), Some(50.0));
+        assert_eq!(timing.output_tokens_per_sec(0), None);
+    }
+}
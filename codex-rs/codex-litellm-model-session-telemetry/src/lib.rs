@@ -3,19 +3,87 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 // See the NOTICE file distributed with this work for attribution details.
 
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 use serde::Serialize;
-use serde_json::json;
+use serde_json::Value;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use latency::LatencySamples;
+use otlp::OtlpExporter;
+use record::{read_log_records, LogRecord, OutcomeRecord};
+
+mod budget;
+mod config;
+mod export;
+mod latency;
+mod metrics;
+mod otlp;
+mod outcome;
+mod pricing;
+mod privacy;
+mod query;
+mod record;
+mod rotation;
+mod sink;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod tags;
+mod workspace;
+mod writer;
+
+pub use budget::{
+    BudgetBreach, BudgetConfig, BudgetExceeded, BudgetLimits, BudgetMetric, BudgetScope,
+    BudgetStatus,
+};
+pub use export::{export_turns, ExportFormat, ExportQuery, EXPORT_COLUMNS};
+pub use latency::TurnTiming;
+pub use metrics::{
+    render_metrics, serve_metrics, write_metrics_textfile, MetricsConfig, MetricsFormat,
+    MetricsServer,
+};
+pub use otlp::OtlpConfig;
+pub use outcome::{ErrorClass, RequestOutcome};
+pub use pricing::{ModelPricing, PricingConfig, PricingTable};
+pub use privacy::{PrivacyConfig, PrivacyMode};
+pub use query::{query_usage, render_usage_table, UsageGroupBy, UsageQuery, UsageRollup};
+pub use record::{read_turn_records, TurnRecord, TURN_RECORD_SCHEMA_VERSION};
+pub use rotation::LogRotation;
+pub use sink::{JsonlSink, MemorySink, TelemetrySink, WebhookConfig, WebhookSink};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteConfig, SqliteStore};
+pub use tags::{parse_session_tag, SessionTags};
+pub use workspace::WorkspaceInfo;
+pub use writer::{flush, shutdown};
 
 const DEFAULT_SESSION_KEY: &str = "_default";
 
+/// `event` value of log lines written by `record_request_outcome`. Lines
+/// without an `event` are turns.
+const REQUEST_OUTCOME_EVENT: &str = "request_outcome";
+
+/// `event` value of the periodic lines written in aggregate privacy mode.
+const ROLLUP_EVENT: &str = "rollup";
+
+/// Name the JSONL log is registered under by `configure_log_file`.
+const JSONL_SINK_NAME: &str = "jsonl";
+
+/// Name the SQLite store is registered under by `configure_sqlite`.
+#[cfg(feature = "sqlite")]
+const SQLITE_SINK_NAME: &str = "sqlite";
+
+/// File name of the session telemetry log under `${CODEX_HOME}/logs`.
+pub const DEFAULT_LOG_FILE_NAME: &str = "codex-litellm-session-telemetry.jsonl";
+
+pub fn default_log_path(codex_home: &Path) -> PathBuf {
+    codex_home.join("logs").join(DEFAULT_LOG_FILE_NAME)
+}
+
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct ModelUsageSnapshot {
     pub model: String,
@@ -24,6 +92,22 @@ pub struct ModelUsageSnapshot {
     pub prompt_tokens: i64,
     pub completion_tokens: i64,
     pub reasoning_tokens: i64,
+    pub cached_tokens: i64,
+    pub cache_creation_tokens: i64,
+    /// Share of prompt tokens served from the provider cache.
+    pub cache_hit_ratio: Option<f64>,
+    /// `None` when no pricing was known for any of this model's turns.
+    pub cost_usd: Option<f64>,
+    pub ttft_p50_ms: Option<i64>,
+    pub ttft_p95_ms: Option<i64>,
+    pub output_tokens_per_sec_p50: Option<f64>,
+    pub output_tokens_per_sec_p95: Option<f64>,
+    /// Requests that still failed after any retries.
+    pub failed_requests: u32,
+    /// Requests re-sent without reasoning after the route rejected it.
+    pub reasoning_retries: u32,
+    /// Every error seen, including ones a retry recovered from.
+    pub errors_by_class: BTreeMap<ErrorClass, u32>,
     pub last_reasoning_effort: Option<String>,
     pub last_updated: DateTime<Utc>,
 }
@@ -35,12 +119,58 @@ pub struct SessionTelemetrySnapshot {
     pub total_prompt_tokens: i64,
     pub total_completion_tokens: i64,
     pub total_reasoning_tokens: i64,
+    pub total_cached_tokens: i64,
+    pub total_cache_creation_tokens: i64,
+    pub cache_hit_ratio: Option<f64>,
+    pub total_cost_usd: Option<f64>,
     pub last_model: Option<String>,
     pub last_reasoning_effort: Option<String>,
     pub last_updated: Option<DateTime<Utc>>,
+    /// Set by `set_session_workspace`, or restored from the log.
+    pub workspace: Option<WorkspaceInfo>,
+    /// Set by `set_session_tags`, or restored from the log.
+    pub tags: SessionTags,
     pub models: Vec<ModelUsageSnapshot>,
 }
 
+/// Token counts reported by the gateway for one turn. Cached and
+/// cache-creation tokens are the parts of `prompt_tokens` that were read from
+/// or written to the provider's prompt cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TurnUsage {
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub reasoning_tokens: i64,
+    pub cached_tokens: i64,
+    pub cache_creation_tokens: i64,
+    pub total_tokens: i64,
+}
+
+/// Everything known about one finished turn, handed to the log writer and
+/// the span exporter.
+#[derive(Clone, Copy)]
+struct TurnEvent<'a> {
+    session_id: Option<&'a str>,
+    model: &'a str,
+    reasoning_effort: Option<&'a str>,
+    gateway_request_id: Option<&'a str>,
+    workspace: Option<&'a WorkspaceInfo>,
+    tags: &'a SessionTags,
+    usage: TurnUsage,
+    timing: Option<TurnTiming>,
+    metrics: TurnMetrics,
+}
+
+/// Values computed when a turn is recorded and persisted alongside it, so a
+/// replayed turn reports what was known at the time.
+#[derive(Debug, Clone, Copy, Default)]
+struct TurnMetrics {
+    cost_usd: Option<f64>,
+    ttft_ms: Option<i64>,
+    duration_ms: Option<i64>,
+    output_tokens_per_sec: Option<f64>,
+}
+
 #[derive(Debug, Default)]
 struct ModelUsageEntry {
     turns: u32,
@@ -48,6 +178,13 @@ struct ModelUsageEntry {
     prompt_tokens: i64,
     completion_tokens: i64,
     reasoning_tokens: i64,
+    cached_tokens: i64,
+    cache_creation_tokens: i64,
+    cost_usd: Option<f64>,
+    latency: LatencySamples,
+    failed_requests: u32,
+    reasoning_retries: u32,
+    errors_by_class: BTreeMap<ErrorClass, u32>,
     last_reasoning_effort: Option<String>,
     last_updated: DateTime<Utc>,
 }
@@ -59,47 +196,105 @@ struct SessionTelemetryEntry {
     total_prompt_tokens: i64,
     total_completion_tokens: i64,
     total_reasoning_tokens: i64,
+    total_cached_tokens: i64,
+    total_cache_creation_tokens: i64,
+    total_cost_usd: Option<f64>,
     last_model: Option<String>,
     last_reasoning_effort: Option<String>,
     last_updated: Option<DateTime<Utc>>,
+    workspace: Option<WorkspaceInfo>,
+    tags: SessionTags,
     models: BTreeMap<String, ModelUsageEntry>,
 }
 
 static TELEMETRY: Lazy<Mutex<HashMap<String, SessionTelemetryEntry>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
-static LOG_FILE: Lazy<Mutex<Option<LogFile>>> = Lazy::new(|| Mutex::new(None));
-
-struct LogFile {
-    writer: BufWriter<File>,
-}
-
-impl LogFile {
-    fn write_turn(
-        &mut self,
-        session_id: Option<&str>,
-        model: &str,
-        reasoning_effort: Option<&str>,
-        prompt_tokens: i64,
-        completion_tokens: i64,
-        reasoning_tokens: i64,
-        total_tokens: i64,
-    ) -> std::io::Result<()> {
-        let record = json!({
-            "ts": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
-            "session_id": session_id,
-            "model": model,
-            "reasoning_effort": reasoning_effort,
-            "prompt_tokens": prompt_tokens,
-            "completion_tokens": completion_tokens,
-            "reasoning_tokens": reasoning_tokens,
-            "total_tokens": total_tokens,
-        });
-        serde_json::to_writer(&mut self.writer, &record)?;
-        self.writer.write_all(b"\n")?;
-        self.writer.flush()?;
-        Ok(())
+/// Registered sinks in registration order, keyed by name.
+type SinkRegistry = Vec<(String, Box<dyn TelemetrySink>)>;
+
+pub(crate) static SINKS: Lazy<Mutex<SinkRegistry>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Path of the JSONL log registered by `configure_log_file`, replayed when a
+/// session resumes.
+static LOG_PATH: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+pub(crate) static LOG_ROTATION: Lazy<Mutex<Option<LogRotation>>> = Lazy::new(|| Mutex::new(None));
+
+static PRICING: Lazy<Mutex<Option<PricingTable>>> = Lazy::new(|| Mutex::new(None));
+
+static BUDGET: Lazy<Mutex<Option<BudgetConfig>>> = Lazy::new(|| Mutex::new(None));
+
+static OTLP_EXPORTER: Lazy<Mutex<Option<OtlpExporter>>> = Lazy::new(|| Mutex::new(None));
+
+static METRICS_TEXTFILE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+/// Usage across all sessions for the current UTC day, seeded from the log the
+/// first time a daily budget is checked.
+static DAILY_USAGE: Lazy<Mutex<Option<DailyUsage>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy)]
+struct DailyUsage {
+    day: NaiveDate,
+    tokens: i64,
+    cost_usd: f64,
+}
+
+/// Sessions whose history has already been replayed from the log file, so a
+/// resumed session is only rebuilt once per process.
+static RESTORED_SESSIONS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+fn turn_record(turn: &TurnEvent<'_>) -> Value {
+    let TurnEvent {
+        usage,
+        timing,
+        metrics,
+        workspace,
+        ..
+    } = *turn;
+    let record = TurnRecord {
+        schema_version: TURN_RECORD_SCHEMA_VERSION,
+        ts: Utc::now(),
+        session_id: turn.session_id.map(str::to_string),
+        model: turn.model.to_string(),
+        reasoning_effort: turn.reasoning_effort.map(str::to_string),
+        gateway_request_id: turn.gateway_request_id.map(str::to_string),
+        cwd: workspace.map(|workspace| workspace.cwd.clone()),
+        git_remote: workspace.and_then(|workspace| workspace.git_remote.clone()),
+        repo: workspace.and_then(|workspace| workspace.repo.clone()),
+        branch: workspace.and_then(|workspace| workspace.branch.clone()),
+        tags: turn.tags.clone(),
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        reasoning_tokens: usage.reasoning_tokens,
+        cached_tokens: usage.cached_tokens,
+        cache_creation_tokens: usage.cache_creation_tokens,
+        total_tokens: usage.total_tokens,
+        cost_usd: metrics.cost_usd,
+        started_at: timing.map(|timing| timing.started_at),
+        first_token_at: timing.and_then(|timing| timing.first_token_at),
+        completed_at: timing.map(|timing| timing.completed_at),
+        ttft_ms: metrics.ttft_ms,
+        duration_ms: metrics.duration_ms,
+        output_tokens_per_sec: metrics.output_tokens_per_sec,
+    };
+    serde_json::to_value(record).unwrap_or(Value::Null)
+}
+
+fn outcome_record(session_id: Option<&str>, model: &str, outcome: &RequestOutcome) -> Value {
+    OutcomeRecord {
+        ts: Utc::now(),
+        session_id: session_id.map(str::to_string),
+        model: model.to_string(),
+        outcome: outcome.clone(),
     }
+    .to_value()
+}
+
+/// Queues a record for every registered sink. Writing happens on the
+/// background writer, never on the caller's thread.
+fn emit(record: Value) {
+    writer::enqueue(privacy::apply(record));
 }
 
 fn normalize_session_id(session_id: Option<&str>) -> String {
@@ -109,94 +304,559 @@ fn normalize_session_id(session_id: Option<&str>) -> String {
         .unwrap_or_else(|| DEFAULT_SESSION_KEY.to_string())
 }
 
+/// Adds a sink, replacing any sink already registered under `name`. Records
+/// already queued are written to the sinks registered when they were queued.
+pub fn register_sink(name: &str, sink: Box<dyn TelemetrySink>) {
+    flush();
+    let mut sinks = SINKS.lock();
+    if let Some(slot) = sinks.iter_mut().find(|(existing, _)| existing == name) {
+        let _ = slot.1.flush();
+        slot.1 = sink;
+    } else {
+        sinks.push((name.to_string(), sink));
+    }
+}
+
+/// Flushes and removes the sink registered under `name`. Returns whether one
+/// was registered.
+pub fn unregister_sink(name: &str) -> bool {
+    flush();
+    let mut sinks = SINKS.lock();
+    let Some(index) = sinks.iter().position(|(existing, _)| existing == name) else {
+        return false;
+    };
+    let (_, mut sink) = sinks.remove(index);
+    let _ = sink.flush();
+    true
+}
+
+/// Registers the JSONL log as the `jsonl` sink. This is also the log replayed
+/// for resumed sessions, daily budgets and usage queries.
 pub fn configure_log_file(path: Option<PathBuf>) -> std::io::Result<()> {
-    let mut guard = LOG_FILE.lock();
+    let mut log_path = LOG_PATH.lock();
     if let Some(path) = path {
-        let mut opts = OpenOptions::new();
-        opts.create(true).append(true).write(true);
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::OpenOptionsExt;
-            opts.mode(0o600);
+        register_sink(JSONL_SINK_NAME, Box::new(JsonlSink::open(path.clone())?));
+        *log_path = Some(path);
+    } else {
+        unregister_sink(JSONL_SINK_NAME);
+        log_path.take();
+    }
+    drop(log_path);
+    forget_restored_sessions();
+    DAILY_USAGE.lock().take();
+    Ok(())
+}
+
+/// Drops the totals of every session rebuilt from the previous log, keeping
+/// its workspace and tags. Each is replayed from the new log on next use;
+/// keeping the old totals would count its logged turns twice.
+fn forget_restored_sessions() {
+    let restored = std::mem::take(&mut *RESTORED_SESSIONS.lock());
+    let mut telemetry = TELEMETRY.lock();
+    for key in restored {
+        if let Some(entry) = telemetry.get_mut(&key) {
+            *entry = SessionTelemetryEntry {
+                workspace: entry.workspace.take(),
+                tags: std::mem::take(&mut entry.tags),
+                ..SessionTelemetryEntry::default()
+            };
+        }
+    }
+}
+
+/// Sets the size/age rotation policy for the telemetry log. Takes effect on
+/// the next write.
+pub fn configure_log_rotation(rotation: Option<LogRotation>) {
+    *LOG_ROTATION.lock() = rotation;
+}
+
+/// Installs the pricing table used to cost each turn at record time. Passing
+/// `None` stops costing new turns; costs already recorded are kept.
+pub fn configure_pricing(table: Option<PricingTable>) {
+    *PRICING.lock() = table;
+}
+
+/// Loads a LiteLLM-style pricing file (JSON or TOML) and installs it.
+pub fn configure_pricing_file(path: Option<&Path>) -> std::io::Result<()> {
+    let table = path.map(PricingTable::load).transpose()?;
+    configure_pricing(table);
+    Ok(())
+}
+
+/// Installs the token and cost budgets checked by `check_budget`.
+pub fn configure_budget(config: Option<BudgetConfig>) {
+    *BUDGET.lock() = config;
+}
+
+/// Rewrites `path` in Prometheus text format after each batch of records the
+/// background writer handles, for node_exporter's textfile collector.
+pub fn configure_metrics_textfile(path: Option<PathBuf>) {
+    *METRICS_TEXTFILE.lock() = path;
+}
+
+fn refresh_metrics_textfile() {
+    if let Some(path) = METRICS_TEXTFILE.lock().as_deref() {
+        if let Err(err) = write_metrics_textfile(path, &labelled_snapshots()) {
+            eprintln!(
+                "[codex-litellm-model-session-telemetry] failed to write metrics textfile: {err}"
+            );
         }
-        let file = opts.open(path)?;
-        *guard = Some(LogFile {
-            writer: BufWriter::new(file),
-        });
+    }
+}
+
+/// Starts exporting one OTLP span per recorded turn. Replacing or clearing
+/// the exporter flushes spans still queued for the previous one.
+pub fn configure_otlp(config: Option<OtlpConfig>) -> std::io::Result<()> {
+    let exporter = config.map(OtlpExporter::start).transpose()?;
+    let previous = std::mem::replace(&mut *OTLP_EXPORTER.lock(), exporter);
+    drop(previous);
+    Ok(())
+}
+
+/// Sets how much of each record reaches the sinks and the span exporter.
+/// Rollups pending under the previous setting are written first.
+pub fn configure_privacy(config: Option<PrivacyConfig>) -> std::io::Result<()> {
+    writer::enqueue(privacy::configure(config)?);
+    Ok(())
+}
+
+/// Registers a SQLite store at `path` as the `sqlite` sink, alongside the
+/// JSONL log.
+#[cfg(feature = "sqlite")]
+pub fn configure_sqlite(path: Option<PathBuf>) -> std::io::Result<()> {
+    if let Some(path) = path {
+        register_sink(SQLITE_SINK_NAME, Box::new(SqliteStore::open(&path)?));
     } else {
-        guard.take();
+        unregister_sink(SQLITE_SINK_NAME);
     }
     Ok(())
 }
 
+fn configured_log_path() -> Option<PathBuf> {
+    LOG_PATH.lock().clone()
+}
+
+/// Calls `visit` with each non-blank line of the log and its rotated
+/// segments, oldest first.
+fn for_each_log_line(path: &Path, mut visit: impl FnMut(&str)) -> std::io::Result<()> {
+    for segment in rotation::log_segments(path) {
+        let reader = match rotation::open_segment(&segment) {
+            Ok(reader) => reader,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                visit(&line);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rebuilds a session's totals from the JSONL log the first time the session
+/// is seen in this process, so resumed sessions report cumulative usage.
+/// Aggregate privacy logs hold only rollups, which belong to no session, so
+/// there a resumed session starts from zero.
+fn restore_session(key: &str) {
+    if key == DEFAULT_SESSION_KEY || privacy::is_aggregate() {
+        return;
+    }
+    let Some(path) = configured_log_path() else {
+        return;
+    };
+    if !RESTORED_SESSIONS.lock().insert(key.to_string()) {
+        return;
+    }
+    // Records still queued for this session would otherwise be missed.
+    flush();
+
+    let logged_key = privacy::session_label(key);
+    let records = match read_log_records(&path, |record| {
+        normalize_session_id(record.session_id()) == logged_key
+    }) {
+        Ok(turns) => turns,
+        Err(err) => {
+            eprintln!(
+                "[codex-litellm-model-session-telemetry] failed to read log for session {key}: {err}"
+            );
+            return;
+        }
+    };
+    if records.is_empty() {
+        return;
+    }
+
+    let mut guard = TELEMETRY.lock();
+    let entry = session_entry(&mut guard, key);
+    let workspace = entry.workspace.take();
+    let tags = std::mem::take(&mut entry.tags);
+    for record in &records {
+        replay_record(entry, record);
+    }
+    // The workspace and tags of this run describe the turns it records.
+    if workspace.is_some() {
+        entry.workspace = workspace;
+    }
+    if !tags.is_empty() {
+        entry.tags = tags;
+    }
+}
+
+/// The entry for `key`. A session first seen in this process starts with
+/// the workspace and tags set for the process as a whole (`session_id`
+/// `None`), so threads started by core carry the CLI's workspace and `--tag`
+/// tags.
+fn session_entry<'a>(
+    telemetry: &'a mut HashMap<String, SessionTelemetryEntry>,
+    key: &str,
+) -> &'a mut SessionTelemetryEntry {
+    if !telemetry.contains_key(key) {
+        let (workspace, tags) = telemetry
+            .get(DEFAULT_SESSION_KEY)
+            .map(|defaults| (defaults.workspace.clone(), defaults.tags.clone()))
+            .unwrap_or_default();
+        telemetry.insert(
+            key.to_string(),
+            SessionTelemetryEntry {
+                workspace,
+                tags,
+                ..SessionTelemetryEntry::default()
+            },
+        );
+    }
+    telemetry.entry(key.to_string()).or_default()
+}
+
+/// Applies a stored turn or request outcome to a session, as `record_turn`
+/// and `record_request_outcome` did when it was first recorded.
+fn replay_record(entry: &mut SessionTelemetryEntry, record: &LogRecord) {
+    match record {
+        LogRecord::Outcome(record) => {
+            apply_outcome(entry, &record.model, &record.outcome, record.ts);
+        }
+        // Rollups carry no session.
+        LogRecord::Rollup(_) => {}
+        LogRecord::Turn(record) => {
+            if let Some(workspace) = record.workspace() {
+                entry.workspace = Some(workspace);
+            }
+            if !record.tags.is_empty() {
+                entry.tags = record.tags.clone();
+            }
+            apply_turn(
+                entry,
+                &record.model,
+                record.reasoning_effort.as_deref(),
+                &record.usage(),
+                &record.metrics(),
+                record.ts,
+            );
+        }
+    }
+}
+
+/// Attributes a session to a workspace. Its later turns carry the cwd, repo
+/// and branch into every sink, export and usage report. For `None` this is
+/// also the workspace of sessions first seen after the call.
+pub fn set_session_workspace(session_id: Option<&str>, workspace: WorkspaceInfo) {
+    let key = normalize_session_id(session_id);
+    restore_session(&key);
+    session_entry(&mut TELEMETRY.lock(), &key).workspace = Some(workspace);
+}
+
+/// Replaces a session's tags. Later turns carry them into every sink and
+/// export; `codex-litellm --tag` also sends them to LiteLLM as metadata. For
+/// `None` these are also the tags of sessions first seen after the call.
+pub fn set_session_tags(session_id: Option<&str>, tags: SessionTags) {
+    let key = normalize_session_id(session_id);
+    restore_session(&key);
+    session_entry(&mut TELEMETRY.lock(), &key).tags = tags;
+}
+
+/// A session's tags; for a session not seen yet, the ones set for `None`.
+/// The core client sends these to LiteLLM with each `/responses` request.
+pub fn session_tags(session_id: Option<&str>) -> SessionTags {
+    let key = normalize_session_id(session_id);
+    let telemetry = TELEMETRY.lock();
+    telemetry
+        .get(&key)
+        .or_else(|| telemetry.get(DEFAULT_SESSION_KEY))
+        .map(|entry| entry.tags.clone())
+        .unwrap_or_default()
+}
+
 pub fn clear_session(session_id: Option<&str>) {
     let key = normalize_session_id(session_id);
     TELEMETRY.lock().remove(&key);
 }
 
+/// Records one completed turn. `gateway_request_id` is the id LiteLLM
+/// returned for the `/responses` call (`x-litellm-call-id`), kept so the turn
+/// can be matched to the gateway's own logs and traces.
 pub fn record_turn(
     session_id: Option<&str>,
     model: &str,
     reasoning_effort: Option<&str>,
-    prompt_tokens: i64,
-    completion_tokens: i64,
-    reasoning_tokens: i64,
-    total_tokens: i64,
+    gateway_request_id: Option<&str>,
+    usage: TurnUsage,
+    timing: Option<TurnTiming>,
 ) {
     let key = normalize_session_id(session_id);
-    let mut guard = TELEMETRY.lock();
-    let entry = guard.entry(key).or_default();
+    restore_session(&key);
+
+    let metrics = TurnMetrics {
+        cost_usd: PRICING
+            .lock()
+            .as_ref()
+            .and_then(|table| table.cost(model, &usage)),
+        ttft_ms: timing.and_then(|timing| timing.ttft_ms()),
+        duration_ms: timing.map(|timing| timing.duration_ms()),
+        output_tokens_per_sec: timing
+            .and_then(|timing| timing.output_tokens_per_sec(usage.completion_tokens)),
+    };
 
+    let mut guard = TELEMETRY.lock();
+    let entry = session_entry(&mut guard, &key);
     let now = Utc::now();
-    let model_entry = entry
-        .models
-        .entry(model.to_string())
-        .or_insert_with(ModelUsageEntry::default);
+    apply_turn(entry, model, reasoning_effort, &usage, &metrics, now);
+    let workspace = entry.workspace.clone();
+    let tags = entry.tags.clone();
+    drop(guard);
+
+    if let Some(daily) = DAILY_USAGE.lock().as_mut() {
+        if daily.day == now.date_naive() {
+            daily.tokens += usage.total_tokens;
+            daily.cost_usd += metrics.cost_usd.unwrap_or(0.0);
+        }
+    }
+
+    let turn = TurnEvent {
+        session_id,
+        model,
+        reasoning_effort,
+        gateway_request_id,
+        workspace: workspace.as_ref(),
+        tags: &tags,
+        usage,
+        timing,
+        metrics,
+    };
+
+    emit(turn_record(&turn));
+
+    if let Some(exporter) = OTLP_EXPORTER.lock().as_ref() {
+        if let Some(session_id) = privacy::span_session_id(session_id) {
+            exporter.export(&TurnEvent {
+                session_id: session_id.as_deref(),
+                ..turn
+            });
+        }
+    }
+}
+
+/// Records how a `/responses` request that hit an error or was retried
+/// finally ended, so failing routes show up per model.
+pub fn record_request_outcome(session_id: Option<&str>, model: &str, outcome: RequestOutcome) {
+    let key = normalize_session_id(session_id);
+    restore_session(&key);
+
+    apply_outcome(
+        session_entry(&mut TELEMETRY.lock(), &key),
+        model,
+        &outcome,
+        Utc::now(),
+    );
+
+    emit(outcome_record(session_id, model, &outcome));
+}
+
+fn apply_outcome(
+    entry: &mut SessionTelemetryEntry,
+    model: &str,
+    outcome: &RequestOutcome,
+    now: DateTime<Utc>,
+) {
+    let model_entry = entry.models.entry(model.to_string()).or_default();
+    if !outcome.succeeded {
+        model_entry.failed_requests = model_entry.failed_requests.saturating_add(1);
+    }
+    if outcome.retried_without_reasoning {
+        model_entry.reasoning_retries = model_entry.reasoning_retries.saturating_add(1);
+    }
+    if let Some(class) = outcome.error_class {
+        let count = model_entry.errors_by_class.entry(class).or_default();
+        *count = count.saturating_add(1);
+    }
+    model_entry.last_updated = now;
+}
+
+fn apply_turn(
+    entry: &mut SessionTelemetryEntry,
+    model: &str,
+    reasoning_effort: Option<&str>,
+    usage: &TurnUsage,
+    metrics: &TurnMetrics,
+    now: DateTime<Utc>,
+) {
+    let model_entry = entry.models.entry(model.to_string()).or_default();
 
     model_entry.turns = model_entry.turns.saturating_add(1);
-    model_entry.total_tokens += total_tokens;
-    model_entry.prompt_tokens += prompt_tokens;
-    model_entry.completion_tokens += completion_tokens;
-    model_entry.reasoning_tokens += reasoning_tokens;
+    model_entry.total_tokens += usage.total_tokens;
+    model_entry.prompt_tokens += usage.prompt_tokens;
+    model_entry.completion_tokens += usage.completion_tokens;
+    model_entry.reasoning_tokens += usage.reasoning_tokens;
+    model_entry.cached_tokens += usage.cached_tokens;
+    model_entry.cache_creation_tokens += usage.cache_creation_tokens;
+    model_entry.cost_usd = add_cost(model_entry.cost_usd, metrics.cost_usd);
+    model_entry
+        .latency
+        .push(metrics.ttft_ms, metrics.output_tokens_per_sec);
     model_entry.last_reasoning_effort = reasoning_effort.map(|effort| effort.to_string());
     model_entry.last_updated = now;
 
     entry.total_turns = entry.total_turns.saturating_add(1);
-    entry.total_tokens += total_tokens;
-    entry.total_prompt_tokens += prompt_tokens;
-    entry.total_completion_tokens += completion_tokens;
-    entry.total_reasoning_tokens += reasoning_tokens;
+    entry.total_tokens += usage.total_tokens;
+    entry.total_prompt_tokens += usage.prompt_tokens;
+    entry.total_completion_tokens += usage.completion_tokens;
+    entry.total_reasoning_tokens += usage.reasoning_tokens;
+    entry.total_cached_tokens += usage.cached_tokens;
+    entry.total_cache_creation_tokens += usage.cache_creation_tokens;
+    entry.total_cost_usd = add_cost(entry.total_cost_usd, metrics.cost_usd);
     entry.last_model = Some(model.to_string());
     entry.last_reasoning_effort = reasoning_effort.map(|effort| effort.to_string());
     entry.last_updated = Some(now);
+}
 
-    drop(guard);
+fn add_cost(total: Option<f64>, cost: Option<f64>) -> Option<f64> {
+    match (total, cost) {
+        (Some(total), Some(cost)) => Some(total + cost),
+        (total, cost) => total.or(cost),
+    }
+}
 
-    {
-        let mut log_guard = LOG_FILE.lock();
-        if let Some(log_file) = log_guard.as_mut() {
-            if let Err(err) = log_file.write_turn(
-                session_id,
-                model,
-                reasoning_effort,
-                prompt_tokens,
-                completion_tokens,
-                reasoning_tokens,
-                total_tokens,
-            ) {
-                eprintln!(
-                    "[codex-litellm-model-session-telemetry] failed to write log entry: {err}"
-                );
-            }
+fn cache_hit_ratio(cached_tokens: i64, prompt_tokens: i64) -> Option<f64> {
+    (prompt_tokens > 0).then(|| cached_tokens as f64 / prompt_tokens as f64)
+}
+
+/// Checks the configured budgets before a turn for `model` is sent. Callers
+/// should warn on `SoftLimitExceeded` and refuse the turn on
+/// `HardLimitExceeded`; `BudgetStatus::into_result` produces the error.
+pub fn check_budget(session_id: Option<&str>, model: &str) -> BudgetStatus {
+    let Some(config) = BUDGET.lock().clone() else {
+        return BudgetStatus::WithinBudget;
+    };
+
+    let key = normalize_session_id(session_id);
+    restore_session(&key);
+
+    let mut usage = budget::BudgetUsage::default();
+    if let Some(entry) = TELEMETRY.lock().get(&key) {
+        usage.session_tokens = entry.total_tokens;
+        usage.session_cost_usd = entry.total_cost_usd.unwrap_or(0.0);
+        if let Some(model_entry) = entry.models.get(model) {
+            usage.model_tokens = model_entry.total_tokens;
+            usage.model_cost_usd = model_entry.cost_usd.unwrap_or(0.0);
         }
     }
+    if config.daily != BudgetLimits::default() {
+        let daily = daily_usage(Utc::now().date_naive());
+        usage.daily_tokens = daily.tokens;
+        usage.daily_cost_usd = daily.cost_usd;
+    }
+
+    config.evaluate(model, &usage)
+}
+
+fn daily_usage(day: NaiveDate) -> DailyUsage {
+    if let Some(daily) = *DAILY_USAGE.lock() {
+        if daily.day == day {
+            return daily;
+        }
+    }
+
+    let mut daily = DailyUsage {
+        day,
+        tokens: 0,
+        cost_usd: 0.0,
+    };
+    if let Some(path) = configured_log_path() {
+        match read_daily_usage(&path, day) {
+            Ok(logged) => daily = logged,
+            Err(err) => eprintln!(
+                "[codex-litellm-model-session-telemetry] failed to read log for daily budget: {err}"
+            ),
+        }
+    }
+    *DAILY_USAGE.lock() = Some(daily);
+    daily
+}
+
+/// Sums the turns logged on `day`. Aggregate-mode rollups count toward the
+/// day their window starts on.
+fn read_daily_usage(path: &Path, day: NaiveDate) -> std::io::Result<DailyUsage> {
+    let mut daily = DailyUsage {
+        day,
+        tokens: 0,
+        cost_usd: 0.0,
+    };
+    let records = read_log_records(path, |record| record.ts().date_naive() == day)?;
+    for record in records {
+        let (tokens, cost_usd) = match record {
+            LogRecord::Turn(turn) => (turn.total_tokens, turn.cost_usd),
+            LogRecord::Rollup(rollup) => (rollup.total_tokens, rollup.cost_usd),
+            LogRecord::Outcome(_) => continue,
+        };
+        daily.tokens += tokens;
+        daily.cost_usd += cost_usd.unwrap_or(0.0);
+    }
+    Ok(daily)
 }
 
 pub fn snapshot(session_id: Option<&str>) -> Option<SessionTelemetrySnapshot> {
     let key = normalize_session_id(session_id);
+    restore_session(&key);
+
     let guard = TELEMETRY.lock();
-    let entry = guard.get(&key)?;
+    guard.get(&key).map(session_snapshot)
+}
+
+/// Snapshots of every session recorded in this process, keyed by session id
+/// (`_default` for turns recorded without one).
+pub fn snapshots() -> BTreeMap<String, SessionTelemetrySnapshot> {
+    TELEMETRY
+        .lock()
+        .iter()
+        .map(|(key, entry)| (key.clone(), session_snapshot(entry)))
+        .collect()
+}
 
+/// `snapshots()` keyed by the session id as the sinks see it, so metrics
+/// never expose an id the privacy level withholds from the log.
+pub(crate) fn labelled_snapshots() -> BTreeMap<String, SessionTelemetrySnapshot> {
+    snapshots()
+        .into_iter()
+        .map(|(key, snapshot)| (privacy::session_label(&key), snapshot))
+        .collect()
+}
+
+/// Session snapshots keyed by repository, then session id. Sessions without
+/// a known repository are listed under `-`.
+pub fn snapshots_by_repo() -> BTreeMap<String, BTreeMap<String, SessionTelemetrySnapshot>> {
+    let mut by_repo: BTreeMap<String, BTreeMap<String, SessionTelemetrySnapshot>> = BTreeMap::new();
+    for (key, snapshot) in snapshots() {
+        let repo = snapshot
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.repo.clone())
+            .unwrap_or_else(|| "-".to_string());
+        by_repo.entry(repo).or_default().insert(key, snapshot);
+    }
+    by_repo
+}
+
+fn session_snapshot(entry: &SessionTelemetryEntry) -> SessionTelemetrySnapshot {
     let mut models: Vec<ModelUsageSnapshot> = entry
         .models
         .iter()
@@ -207,22 +867,226 @@ pub fn snapshot(session_id: Option<&str>) -> Option<SessionTelemetrySnapshot> {
             prompt_tokens: usage.prompt_tokens,
             completion_tokens: usage.completion_tokens,
             reasoning_tokens: usage.reasoning_tokens,
+            cached_tokens: usage.cached_tokens,
+            cache_creation_tokens: usage.cache_creation_tokens,
+            cache_hit_ratio: cache_hit_ratio(usage.cached_tokens, usage.prompt_tokens),
+            cost_usd: usage.cost_usd,
+            ttft_p50_ms: usage.latency.ttft_percentile_ms(50.0),
+            ttft_p95_ms: usage.latency.ttft_percentile_ms(95.0),
+            output_tokens_per_sec_p50: usage.latency.output_tokens_per_sec_percentile(50.0),
+            output_tokens_per_sec_p95: usage.latency.output_tokens_per_sec_percentile(95.0),
+            failed_requests: usage.failed_requests,
+            reasoning_retries: usage.reasoning_retries,
+            errors_by_class: usage.errors_by_class.clone(),
             last_reasoning_effort: usage.last_reasoning_effort.clone(),
             last_updated: usage.last_updated,
         })
         .collect();
 
-    models.sort_by(|a, b| b.total_tokens.cmp(&a.total_tokens));
+    models.sort_by_key(|usage| std::cmp::Reverse(usage.total_tokens));
 
-    Some(SessionTelemetrySnapshot {
+    SessionTelemetrySnapshot {
         total_turns: entry.total_turns,
         total_tokens: entry.total_tokens,
         total_prompt_tokens: entry.total_prompt_tokens,
         total_completion_tokens: entry.total_completion_tokens,
         total_reasoning_tokens: entry.total_reasoning_tokens,
+        total_cached_tokens: entry.total_cached_tokens,
+        total_cache_creation_tokens: entry.total_cache_creation_tokens,
+        cache_hit_ratio: cache_hit_ratio(entry.total_cached_tokens, entry.total_prompt_tokens),
+        total_cost_usd: entry.total_cost_usd,
         last_model: entry.last_model.clone(),
         last_reasoning_effort: entry.last_reasoning_effort.clone(),
         last_updated: entry.last_updated,
+        workspace: entry.workspace.clone(),
+        tags: entry.tags.clone(),
         models,
-    })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Held by tests that point the process at a telemetry log.
+    static LOG_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    fn turn(prompt_tokens: i64, cached_tokens: i64) -> TurnUsage {
+        TurnUsage {
+            prompt_tokens,
+            cached_tokens,
+            completion_tokens: 10,
+            total_tokens: prompt_tokens + 10,
+            ..TurnUsage::default()
+        }
+    }
+
+    #[test]
+    fn cache_hit_ratio_is_unknown_until_a_turn_has_input() {
+        let mut entry = SessionTelemetryEntry::default();
+        let now = Utc::now();
+        apply_turn(
+            &mut entry,
+            "gpt-5",
+            None,
+            &turn(0, 0),
+            &TurnMetrics::default(),
+            now,
+        );
+
+        let snapshot = session_snapshot(&entry);
+        assert_eq!(snapshot.cache_hit_ratio, None);
+        assert_eq!(snapshot.models[0].cache_hit_ratio, None);
+
+        apply_turn(
+            &mut entry,
+            "gpt-5",
+            None,
+            &turn(400, 300),
+            &TurnMetrics::default(),
+            now,
+        );
+        apply_turn(
+            &mut entry,
+            "gpt-5",
+            None,
+            &turn(0, 0),
+            &TurnMetrics::default(),
+            now,
+        );
+
+        let snapshot = session_snapshot(&entry);
+        assert_eq!(snapshot.cache_hit_ratio, Some(0.75));
+        assert_eq!(snapshot.models[0].cache_hit_ratio, Some(0.75));
+        assert_eq!(cache_hit_ratio(5, -1), None);
+    }
+
+    #[test]
+    fn reconfiguring_the_log_keeps_resumed_totals_cumulative() {
+        let _log = LOG_TEST_LOCK.lock();
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        let session = Some("resume-reconfigure-session");
+        let workspace = WorkspaceInfo {
+            cwd: dir.path().to_path_buf(),
+            git_remote: None,
+            repo: Some("acme/widgets".to_string()),
+            branch: None,
+        };
+        let total = |snapshot: Option<SessionTelemetrySnapshot>| {
+            snapshot.map(|snapshot| (snapshot.total_turns, snapshot.total_tokens))
+        };
+
+        configure_log_file(Some(path.clone())).expect("configure log");
+        set_session_workspace(session, workspace.clone());
+        record_turn(session, "gpt-5", None, None, turn(100, 0), None);
+        record_turn(session, "gpt-5", None, None, turn(200, 0), None);
+        assert_eq!(total(snapshot(session)), Some((2, 320)));
+
+        // Reopening the same log replays it instead of adding to the totals.
+        configure_log_file(Some(path.clone())).expect("reconfigure log");
+        record_turn(session, "gpt-5", None, None, turn(50, 0), None);
+        assert_eq!(total(snapshot(session)), Some((3, 380)));
+        assert_eq!(
+            snapshot(session).and_then(|snapshot| snapshot.workspace),
+            Some(workspace)
+        );
+
+        // A process that resumes the session sees the same totals.
+        clear_session(session);
+        configure_log_file(Some(path.clone())).expect("resume log");
+        assert_eq!(total(snapshot(session)), Some((3, 380)));
+
+        configure_log_file(None).expect("unset log");
+        clear_session(session);
+    }
+
+    #[test]
+    fn a_resumed_thread_replays_its_turns_and_takes_this_runs_labels() {
+        let _log = LOG_TEST_LOCK.lock();
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        let thread = Some("0199a1b2-resumed-thread");
+        let workspace = |repo: &str| WorkspaceInfo {
+            cwd: dir.path().to_path_buf(),
+            git_remote: None,
+            repo: Some(repo.to_string()),
+            branch: None,
+        };
+        let tags =
+            |key: &str, value: &str| SessionTags::from([(key.to_string(), value.to_string())]);
+        let start_run = |repo: &str, tag: (&str, &str)| {
+            // What `codex-litellm` does at startup, before core starts a thread.
+            configure_log_file(Some(path.clone())).expect("configure log");
+            set_session_workspace(None, workspace(repo));
+            set_session_tags(None, tags(tag.0, tag.1));
+        };
+
+        start_run("acme/widgets", ("ticket", "ENG-1"));
+        // What the core client sends with, and records after, each
+        // `/responses` request.
+        assert_eq!(session_tags(thread), tags("ticket", "ENG-1"));
+        record_turn(
+            thread,
+            "gpt-5",
+            Some("high"),
+            Some("call-1"),
+            turn(100, 0),
+            None,
+        );
+        record_turn(
+            thread,
+            "gpt-5",
+            Some("high"),
+            Some("call-2"),
+            turn(200, 0),
+            None,
+        );
+        let first = snapshot(thread).expect("thread snapshot");
+        assert_eq!((first.total_turns, first.total_tokens), (2, 320));
+        assert_eq!(first.workspace, Some(workspace("acme/widgets")));
+        assert_eq!(first.tags, tags("ticket", "ENG-1"));
+
+        // A new process resumes the thread with other labels.
+        flush();
+        clear_session(thread);
+        clear_session(None);
+        start_run("acme/gadgets", ("ticket", "ENG-2"));
+        record_turn(
+            thread,
+            "gpt-5",
+            Some("low"),
+            Some("call-3"),
+            turn(50, 0),
+            None,
+        );
+        let resumed = snapshot(thread).expect("resumed snapshot");
+        assert_eq!((resumed.total_turns, resumed.total_tokens), (3, 380));
+        assert_eq!(resumed.last_reasoning_effort.as_deref(), Some("low"));
+        assert_eq!(resumed.workspace, Some(workspace("acme/gadgets")));
+        assert_eq!(resumed.tags, tags("ticket", "ENG-2"));
+
+        configure_log_file(None).expect("unset log");
+        clear_session(thread);
+        clear_session(None);
+    }
+
+    #[test]
+    fn daily_usage_sums_turns_and_rollups_of_the_day() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        std::fs::write(
+            &path,
+            r#"{"ts":"2025-03-01T23:59:00.000Z","model":"m","total_tokens":5,"cost_usd":0.5}
+{"ts":"2025-03-02T00:10:00.000Z","model":"m","total_tokens":10,"cost_usd":1.0}
+{"event":"request_outcome","ts":"2025-03-02T00:20:00.000Z","model":"m","succeeded":false}
+{"event":"rollup","ts":"2025-03-02T01:00:00.000Z","period_end":"2025-03-02T02:00:00.000Z","model":"m","turns":2,"total_tokens":30,"cost_usd":0.25}
+"#,
+        )
+        .expect("write log");
+
+        let daily = read_daily_usage(&path, "2025-03-02".parse().expect("day")).expect("read");
+        assert_eq!((daily.tokens, daily.cost_usd), (40, 1.25));
+    }
 }
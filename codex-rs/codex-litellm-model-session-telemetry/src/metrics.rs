@@ -0,0 +1,424 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::config::read_config_table;
+use crate::SessionTelemetrySnapshot;
+
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+
+/// `[telemetry.metrics]` in `config.toml`:
+///
+/// ```toml
+/// [telemetry.metrics]
+/// listen = "127.0.0.1:9464"
+/// textfile = "/var/lib/node_exporter/textfile/codex-litellm.prom"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub listen: Option<String>,
+    #[serde(default)]
+    pub textfile: Option<PathBuf>,
+}
+
+impl MetricsConfig {
+    pub fn from_config_toml(path: &Path) -> io::Result<Option<Self>> {
+        read_config_table(path, &["telemetry", "metrics"])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsFormat {
+    /// OpenMetrics 1.0, served over HTTP.
+    OpenMetrics,
+    /// Prometheus text format 0.0.4, as read by node_exporter's textfile
+    /// collector.
+    PrometheusText,
+}
+
+struct Family {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    samples: Vec<(Vec<(&'static str, String)>, f64)>,
+}
+
+impl Family {
+    fn new(name: &'static str, kind: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            kind,
+            help,
+            samples: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, labels: Vec<(&'static str, String)>, value: f64) {
+        self.samples.push((labels, value));
+    }
+}
+
+/// Renders session aggregates as counters and gauges labelled by
+/// `session_id` and `model`.
+pub fn render_metrics(
+    sessions: &BTreeMap<String, SessionTelemetrySnapshot>,
+    format: MetricsFormat,
+) -> String {
+    let mut turns = Family::new("codex_litellm_turns", "counter", "Completed turns.");
+    let mut tokens = Family::new(
+        "codex_litellm_tokens",
+        "counter",
+        "Tokens reported by the gateway, by type.",
+    );
+    let mut cost = Family::new(
+        "codex_litellm_cost_usd",
+        "counter",
+        "Estimated spend in US dollars from the configured pricing table.",
+    );
+    let mut ttft = Family::new(
+        "codex_litellm_time_to_first_token_seconds",
+        "gauge",
+        "Time to first token over recent turns.",
+    );
+    let mut throughput = Family::new(
+        "codex_litellm_output_tokens_per_second",
+        "gauge",
+        "Output token throughput over recent turns.",
+    );
+    let mut failed = Family::new(
+        "codex_litellm_failed_requests",
+        "counter",
+        "Requests that still failed after retries.",
+    );
+    let mut errors = Family::new(
+        "codex_litellm_request_errors",
+        "counter",
+        "Request errors by class, including ones a retry recovered from.",
+    );
+    let mut reasoning_retries = Family::new(
+        "codex_litellm_reasoning_retries",
+        "counter",
+        "Requests re-sent without reasoning after the route rejected it.",
+    );
+    let mut sessions_gauge = Family::new(
+        "codex_litellm_sessions",
+        "gauge",
+        "Sessions with recorded turns in this process.",
+    );
+    sessions_gauge.push(Vec::new(), sessions.len() as f64);
+
+    for (session_id, session) in sessions {
+        for model in &session.models {
+            let labels = || {
+                vec![
+                    ("session_id", session_id.clone()),
+                    ("model", model.model.clone()),
+                ]
+            };
+            turns.push(labels(), f64::from(model.turns));
+            for (kind, value) in [
+                ("prompt", model.prompt_tokens),
+                ("completion", model.completion_tokens),
+                ("reasoning", model.reasoning_tokens),
+                ("cached", model.cached_tokens),
+                ("cache_creation", model.cache_creation_tokens),
+            ] {
+                let mut labels = labels();
+                labels.push(("type", kind.to_string()));
+                tokens.push(labels, value as f64);
+            }
+            if let Some(value) = model.cost_usd {
+                cost.push(labels(), value);
+            }
+            for (quantile, value) in [("0.5", model.ttft_p50_ms), ("0.95", model.ttft_p95_ms)] {
+                if let Some(value) = value {
+                    let mut labels = labels();
+                    labels.push(("quantile", quantile.to_string()));
+                    ttft.push(labels, value as f64 / 1000.0);
+                }
+            }
+            for (quantile, value) in [
+                ("0.5", model.output_tokens_per_sec_p50),
+                ("0.95", model.output_tokens_per_sec_p95),
+            ] {
+                if let Some(value) = value {
+                    let mut labels = labels();
+                    labels.push(("quantile", quantile.to_string()));
+                    throughput.push(labels, value);
+                }
+            }
+            if model.failed_requests > 0 {
+                failed.push(labels(), f64::from(model.failed_requests));
+            }
+            if model.reasoning_retries > 0 {
+                reasoning_retries.push(labels(), f64::from(model.reasoning_retries));
+            }
+            for (class, count) in &model.errors_by_class {
+                let mut labels = labels();
+                labels.push(("class", class.to_string()));
+                errors.push(labels, f64::from(*count));
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for family in [
+        turns,
+        tokens,
+        cost,
+        ttft,
+        throughput,
+        failed,
+        errors,
+        reasoning_retries,
+        sessions_gauge,
+    ] {
+        write_family(&mut out, &family, format);
+    }
+    if format == MetricsFormat::OpenMetrics {
+        out.push_str("# EOF\n");
+    }
+    out
+}
+
+fn write_family(out: &mut String, family: &Family, format: MetricsFormat) {
+    let sample_name = if family.kind == "counter" {
+        format!("{}_total", family.name)
+    } else {
+        family.name.to_string()
+    };
+    // OpenMetrics names the counter family without `_total`; the Prometheus
+    // text format names it after the sample.
+    let family_name = match format {
+        MetricsFormat::OpenMetrics => family.name.to_string(),
+        MetricsFormat::PrometheusText => sample_name.clone(),
+    };
+    let _ = writeln!(out, "# TYPE {family_name} {}", family.kind);
+    let _ = writeln!(out, "# HELP {family_name} {}", family.help);
+    for (labels, value) in &family.samples {
+        out.push_str(&sample_name);
+        if !labels.is_empty() {
+            let rendered = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_label_value(value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = write!(out, "{{{rendered}}}");
+        }
+        let _ = writeln!(out, " {value}");
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Atomically replaces `path` with the current metrics in Prometheus text
+/// format, for node_exporter's textfile collector.
+pub fn write_metrics_textfile(
+    path: &Path,
+    sessions: &BTreeMap<String, SessionTelemetrySnapshot>,
+) -> io::Result<()> {
+    let rendered = render_metrics(sessions, MetricsFormat::PrometheusText);
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, rendered)?;
+    fs::rename(&tmp, path)
+}
+
+/// A local HTTP listener serving `GET /metrics`. Dropping it stops the
+/// listener thread.
+pub struct MetricsServer {
+    local_addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn stop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake the blocking accept so the thread notices the flag.
+        let _ = TcpStream::connect_timeout(&self.local_addr, Duration::from_millis(200));
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Starts serving metrics on `addr`. Bind to `127.0.0.1:0` to pick a free
+/// port; `local_addr` reports the one chosen.
+pub fn serve_metrics(addr: impl ToSocketAddrs) -> io::Result<MetricsServer> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let thread_shutdown = Arc::clone(&shutdown);
+    let handle = std::thread::Builder::new()
+        .name("codex-litellm-metrics".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                if thread_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if let Err(err) = handle_connection(stream) {
+                    eprintln!(
+                        "[codex-litellm-model-session-telemetry] metrics request failed: {err}"
+                    );
+                }
+            }
+        })?;
+    Ok(MetricsServer {
+        local_addr,
+        shutdown,
+        handle: Some(handle),
+    })
+}
+
+fn handle_connection(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+        if head.len() > MAX_REQUEST_HEAD_BYTES {
+            break;
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default();
+
+    let (status, content_type, body) = if method != "GET" {
+        ("405 Method Not Allowed", "text/plain", String::new())
+    } else if path == "/metrics" {
+        (
+            "200 OK",
+            OPENMETRICS_CONTENT_TYPE,
+            render_metrics(&crate::labelled_snapshots(), MetricsFormat::OpenMetrics),
+        )
+    } else {
+        ("404 Not Found", "text/plain", String::new())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{record_turn, TurnUsage};
+
+    fn scrape(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).expect("connect to metrics listener");
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").expect("send request");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .expect("read metrics response");
+        response
+    }
+
+    #[test]
+    fn serves_recorded_turns_as_openmetrics_over_localhost() {
+        record_turn(
+            Some("metrics-scrape-session"),
+            "vercel/minimax-m2.7",
+            Some("medium"),
+            None,
+            TurnUsage {
+                prompt_tokens: 120,
+                completion_tokens: 30,
+                reasoning_tokens: 10,
+                cached_tokens: 100,
+                cache_creation_tokens: 0,
+                total_tokens: 150,
+            },
+            None,
+        );
+
+        let server = serve_metrics("127.0.0.1:0").expect("bind metrics listener");
+        let response = scrape(server.local_addr(), "/metrics");
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(OPENMETRICS_CONTENT_TYPE));
+        assert!(response.contains("# TYPE codex_litellm_turns counter\n"));
+        assert!(response.contains(
+            "codex_litellm_turns_total{session_id=\"metrics-scrape-session\",model=\"vercel/minimax-m2.7\"} 1\n"
+        ));
+        assert!(response.contains(
+            "codex_litellm_tokens_total{session_id=\"metrics-scrape-session\",model=\"vercel/minimax-m2.7\",type=\"cached\"} 100\n"
+        ));
+        assert!(response.ends_with("# EOF\n"));
+
+        let missing = scrape(server.local_addr(), "/other");
+        assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn textfile_uses_prometheus_counter_names_and_escapes_labels() {
+        let mut sessions = BTreeMap::new();
+        sessions.insert(
+            "quoted\"session".to_string(),
+            SessionTelemetrySnapshot {
+                models: vec![crate::ModelUsageSnapshot {
+                    model: "m".to_string(),
+                    turns: 2,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("codex-litellm.prom");
+
+        write_metrics_textfile(&path, &sessions).expect("write textfile");
+        let rendered = fs::read_to_string(&path).expect("read textfile");
+
+        assert!(rendered.contains("# TYPE codex_litellm_turns_total counter\n"));
+        assert!(rendered.contains(
+            "codex_litellm_turns_total{session_id=\"quoted\\\"session\",model=\"m\"} 2\n"
+        ));
+        assert!(!rendered.contains("# EOF"));
+    }
+}
@@ -0,0 +1,374 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::config::read_config_table;
+use crate::TurnEvent;
+
+/// Spans waiting to be exported. When the collector falls behind, new spans
+/// are dropped rather than holding up the turn.
+const QUEUE_CAPACITY: usize = 1024;
+const MAX_BATCH: usize = 128;
+const SCOPE_NAME: &str = "codex-litellm-model-session-telemetry";
+
+fn default_service_name() -> String {
+    "codex-litellm".to_string()
+}
+
+fn default_timeout_ms() -> u64 {
+    10_000
+}
+
+/// `[telemetry.otlp]` in `config.toml`:
+///
+/// ```toml
+/// [telemetry.otlp]
+/// endpoint = "http://127.0.0.1:4318"
+/// headers = { "x-api-key" = "..." }
+/// ```
+///
+/// Spans are sent as OTLP/HTTP JSON to `{endpoint}/v1/traces`, unless the
+/// endpoint already ends in `/v1/traces`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OtlpConfig {
+    pub endpoint: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl OtlpConfig {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            headers: BTreeMap::new(),
+            service_name: default_service_name(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+
+    pub fn from_config_toml(path: &Path) -> io::Result<Option<Self>> {
+        read_config_table(path, &["telemetry", "otlp"])
+    }
+
+    fn traces_url(&self) -> String {
+        let endpoint = self.endpoint.trim_end_matches('/');
+        if endpoint.ends_with("/v1/traces") {
+            endpoint.to_string()
+        } else {
+            format!("{endpoint}/v1/traces")
+        }
+    }
+}
+
+/// Background thread posting span batches. Dropping it closes the queue and
+/// waits for spans already queued to be sent.
+pub(crate) struct OtlpExporter {
+    sender: Option<SyncSender<Value>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl OtlpExporter {
+    pub(crate) fn start(config: OtlpConfig) -> io::Result<Self> {
+        if !config.endpoint.starts_with("http://") && !config.endpoint.starts_with("https://") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "OTLP endpoint must be an http:// or https:// URL, got `{}`",
+                    config.endpoint
+                ),
+            ));
+        }
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let handle = std::thread::Builder::new()
+            .name("codex-litellm-otlp".to_string())
+            .spawn(move || export_loop(&config, &receiver))?;
+        Ok(Self {
+            sender: Some(sender),
+            handle: Some(handle),
+        })
+    }
+
+    pub(crate) fn export(&self, turn: &TurnEvent<'_>) {
+        let Some(sender) = self.sender.as_ref() else {
+            return;
+        };
+        match sender.try_send(turn_span(turn, Utc::now())) {
+            Ok(()) | Err(TrySendError::Disconnected(_)) => {}
+            Err(TrySendError::Full(_)) => {
+                eprintln!("[codex-litellm-model-session-telemetry] OTLP queue full, dropping span");
+            }
+        }
+    }
+}
+
+impl Drop for OtlpExporter {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn export_loop(config: &OtlpConfig, receiver: &Receiver<Value>) {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_millis(config.timeout_ms))
+        .build();
+    let url = config.traces_url();
+    while let Ok(span) = receiver.recv() {
+        let mut spans = vec![span];
+        while spans.len() < MAX_BATCH {
+            match receiver.try_recv() {
+                Ok(span) => spans.push(span),
+                Err(_) => break,
+            }
+        }
+        let body = export_request(&config.service_name, spans).to_string();
+        let mut request = agent.post(&url).set("Content-Type", "application/json");
+        for (name, value) in &config.headers {
+            request = request.set(name, value);
+        }
+        if let Err(err) = request.send_string(&body) {
+            eprintln!("[codex-litellm-model-session-telemetry] OTLP export to {url} failed: {err}");
+        }
+    }
+}
+
+fn export_request(service_name: &str, spans: Vec<Value>) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [string_attribute("service.name", service_name)],
+            },
+            "scopeSpans": [{
+                "scope": {
+                    "name": SCOPE_NAME,
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+/// Builds a client span for the `/responses` request using the OpenTelemetry
+/// GenAI semantic conventions. Values without a convention yet use the
+/// `codex_litellm.` prefix.
+fn turn_span(turn: &TurnEvent<'_>, now: DateTime<Utc>) -> Value {
+    let started_at = turn.timing.map_or(now, |timing| timing.started_at);
+    let completed_at = turn.timing.map_or(now, |timing| timing.completed_at);
+
+    let mut attributes = vec![
+        string_attribute("gen_ai.operation.name", "chat"),
+        string_attribute("gen_ai.provider.name", "litellm"),
+        string_attribute("gen_ai.request.model", turn.model),
+        int_attribute("gen_ai.usage.input_tokens", turn.usage.prompt_tokens),
+        int_attribute("gen_ai.usage.output_tokens", turn.usage.completion_tokens),
+        int_attribute(
+            "codex_litellm.usage.reasoning_tokens",
+            turn.usage.reasoning_tokens,
+        ),
+        int_attribute(
+            "codex_litellm.usage.cached_tokens",
+            turn.usage.cached_tokens,
+        ),
+        int_attribute(
+            "codex_litellm.usage.cache_creation_tokens",
+            turn.usage.cache_creation_tokens,
+        ),
+        int_attribute("codex_litellm.usage.total_tokens", turn.usage.total_tokens),
+    ];
+    if let Some(effort) = turn.reasoning_effort {
+        attributes.push(string_attribute("codex_litellm.reasoning_effort", effort));
+    }
+    if let Some(request_id) = turn.gateway_request_id {
+        attributes.push(string_attribute("gen_ai.response.id", request_id));
+    }
+    if let Some(session_id) = turn.session_id {
+        attributes.push(string_attribute("session.id", session_id));
+    }
+    if let Some(cost) = turn.metrics.cost_usd {
+        attributes
+            .push(json!({ "key": "codex_litellm.cost_usd", "value": { "doubleValue": cost } }));
+    }
+    if let Some(ttft_ms) = turn.metrics.ttft_ms {
+        attributes.push(int_attribute("codex_litellm.ttft_ms", ttft_ms));
+    }
+
+    json!({
+        "traceId": random_hex_id(2),
+        "spanId": random_hex_id(1),
+        "name": format!("chat {}", turn.model),
+        // SPAN_KIND_CLIENT
+        "kind": 3,
+        "startTimeUnixNano": unix_nanos(started_at),
+        "endTimeUnixNano": unix_nanos(completed_at),
+        "attributes": attributes,
+    })
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+// OTLP/JSON encodes 64-bit integers as strings.
+fn int_attribute(key: &str, value: i64) -> Value {
+    json!({ "key": key, "value": { "intValue": value.to_string() } })
+}
+
+fn unix_nanos(ts: DateTime<Utc>) -> String {
+    ts.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+/// Hex id made of `words` random 64-bit words: 2 for a trace id, 1 for a
+/// span id.
+fn random_hex_id(words: usize) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    (0..words)
+        .map(|_| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{configure_otlp, record_turn, TurnTiming, TurnUsage};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Accepts OTLP/HTTP posts on localhost and hands back each body.
+    fn collector_stand_in() -> (String, mpsc::Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind collector");
+        let endpoint = format!("http://{}", listener.local_addr().expect("local addr"));
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+                let mut content_length = 0;
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).expect("request line");
+                assert!(request_line.starts_with("POST /v1/traces "));
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("header line");
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().expect("content length");
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).expect("body");
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}",
+                    )
+                    .expect("respond");
+                let _ = sender.send(serde_json::from_slice(&body).expect("json body"));
+            }
+        });
+        (endpoint, receiver)
+    }
+
+    #[test]
+    fn exports_a_genai_span_per_turn_to_the_collector() {
+        let (endpoint, bodies) = collector_stand_in();
+        configure_otlp(Some(OtlpConfig::new(endpoint))).expect("start exporter");
+
+        let started_at = Utc::now();
+        record_turn(
+            Some("otlp-span-session"),
+            "vercel/minimax-m2.7",
+            Some("high"),
+            Some("litellm-call-123"),
+            TurnUsage {
+                prompt_tokens: 200,
+                completion_tokens: 40,
+                reasoning_tokens: 12,
+                cached_tokens: 0,
+                cache_creation_tokens: 0,
+                total_tokens: 240,
+            },
+            Some(TurnTiming {
+                started_at,
+                first_token_at: None,
+                completed_at: started_at + chrono::Duration::milliseconds(750),
+            }),
+        );
+        // Clearing the exporter flushes the queue before returning.
+        configure_otlp(None).expect("stop exporter");
+
+        let span = std::iter::from_fn(|| bodies.recv_timeout(Duration::from_secs(5)).ok())
+            .flat_map(|body| {
+                body["resourceSpans"][0]["scopeSpans"][0]["spans"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .find(|span| {
+                span["attributes"].as_array().is_some_and(|attrs| {
+                    attrs.contains(&string_attribute("session.id", "otlp-span-session"))
+                })
+            })
+            .expect("span for the recorded turn");
+        let attributes = span["attributes"].as_array().expect("attributes");
+
+        assert_eq!(span["name"], "chat vercel/minimax-m2.7");
+        assert_eq!(span["kind"], 3);
+        assert_eq!(span["traceId"].as_str().map(str::len), Some(32));
+        assert_eq!(span["spanId"].as_str().map(str::len), Some(16));
+        assert_eq!(span["startTimeUnixNano"], json!(unix_nanos(started_at)));
+        for expected in [
+            string_attribute("gen_ai.request.model", "vercel/minimax-m2.7"),
+            int_attribute("gen_ai.usage.input_tokens", 200),
+            int_attribute("gen_ai.usage.output_tokens", 40),
+            int_attribute("codex_litellm.usage.reasoning_tokens", 12),
+            string_attribute("codex_litellm.reasoning_effort", "high"),
+            string_attribute("gen_ai.provider.name", "litellm"),
+            string_attribute("gen_ai.response.id", "litellm-call-123"),
+        ] {
+            assert!(attributes.contains(&expected), "missing {expected}");
+        }
+    }
+
+    #[test]
+    fn traces_url_appends_the_signal_path_once() {
+        assert_eq!(
+            OtlpConfig::new("http://collector:4318/").traces_url(),
+            "http://collector:4318/v1/traces"
+        );
+        assert_eq!(
+            OtlpConfig::new("http://collector:4318/v1/traces").traces_url(),
+            "http://collector:4318/v1/traces"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Coarse reason a `/responses` request to the gateway failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// The route rejected `reasoning_effort` (LiteLLM `UnsupportedParamsError`).
+    ReasoningRejected,
+    BadRequest,
+    Unauthorized,
+    RateLimited,
+    ServerError,
+    Timeout,
+    /// Connection, DNS or TLS failure before a response arrived.
+    Transport,
+    /// The response stream broke or carried an error event.
+    Stream,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn from_http_status(status: u16) -> Self {
+        match status {
+            401 | 403 => ErrorClass::Unauthorized,
+            408 | 504 => ErrorClass::Timeout,
+            429 => ErrorClass::RateLimited,
+            400..=499 => ErrorClass::BadRequest,
+            500..=599 => ErrorClass::ServerError,
+            _ => ErrorClass::Other,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorClass::ReasoningRejected => "reasoning_rejected",
+            ErrorClass::BadRequest => "bad_request",
+            ErrorClass::Unauthorized => "unauthorized",
+            ErrorClass::RateLimited => "rate_limited",
+            ErrorClass::ServerError => "server_error",
+            ErrorClass::Timeout => "timeout",
+            ErrorClass::Transport => "transport",
+            ErrorClass::Stream => "stream",
+            ErrorClass::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How a `/responses` request ended after any retries. Only requests that hit
+/// an error or were retried need to be recorded; plain successes are already
+/// covered by `record_turn`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestOutcome {
+    /// The last error seen, including one that a retry recovered from.
+    #[serde(default)]
+    pub error_class: Option<ErrorClass>,
+    #[serde(default)]
+    pub http_status: Option<u16>,
+    /// The request was re-sent without `reasoning` after the route rejected it.
+    #[serde(default)]
+    pub retried_without_reasoning: bool,
+    #[serde(default)]
+    pub succeeded: bool,
+    #[serde(default)]
+    pub gateway_request_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apply_outcome, session_snapshot, SessionTelemetryEntry};
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn http_statuses_map_to_error_classes() {
+        let classes: Vec<(u16, ErrorClass)> = [400, 401, 403, 404, 408, 429, 500, 504, 302]
+            .into_iter()
+            .map(|status| (status, ErrorClass::from_http_status(status)))
+            .collect();
+        assert_eq!(
+            classes,
+            vec![
+                (400, ErrorClass::BadRequest),
+                (401, ErrorClass::Unauthorized),
+                (403, ErrorClass::Unauthorized),
+                (404, ErrorClass::BadRequest),
+                (408, ErrorClass::Timeout),
+                (429, ErrorClass::RateLimited),
+                (500, ErrorClass::ServerError),
+                (504, ErrorClass::Timeout),
+                (302, ErrorClass::Other),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(ErrorClass::ReasoningRejected).expect("serialize"),
+            ErrorClass::ReasoningRejected.as_str()
+        );
+    }
+
+    #[test]
+    fn failures_retries_and_error_classes_are_counted_per_model() {
+        let mut entry = SessionTelemetryEntry::default();
+        let now = Utc::now();
+        let outcomes = [
+            RequestOutcome {
+                error_class: Some(ErrorClass::ReasoningRejected),
+                http_status: Some(400),
+                retried_without_reasoning: true,
+                succeeded: true,
+                gateway_request_id: None,
+            },
+            RequestOutcome {
+                error_class: Some(ErrorClass::RateLimited),
+                http_status: Some(429),
+                ..RequestOutcome::default()
+            },
+            RequestOutcome {
+                error_class: Some(ErrorClass::RateLimited),
+                http_status: Some(429),
+                ..RequestOutcome::default()
+            },
+            RequestOutcome {
+                error_class: Some(ErrorClass::Stream),
+                retried_without_reasoning: true,
+                ..RequestOutcome::default()
+            },
+        ];
+        for outcome in &outcomes {
+            apply_outcome(&mut entry, "gpt-5", outcome, now);
+        }
+        apply_outcome(
+            &mut entry,
+            "claude-sonnet-4",
+            &RequestOutcome {
+                error_class: Some(ErrorClass::Transport),
+                ..RequestOutcome::default()
+            },
+            now,
+        );
+
+        let counters: Vec<_> = session_snapshot(&entry)
+            .models
+            .into_iter()
+            .map(|usage| {
+                (
+                    usage.model,
+                    usage.failed_requests,
+                    usage.reasoning_retries,
+                    usage.errors_by_class,
+                )
+            })
+            .collect();
+        assert_eq!(
+            counters,
+            vec![
+                (
+                    "claude-sonnet-4".to_string(),
+                    1,
+                    0,
+                    BTreeMap::from([(ErrorClass::Transport, 1)]),
+                ),
+                (
+                    "gpt-5".to_string(),
+                    3,
+                    2,
+                    BTreeMap::from([
+                        (ErrorClass::ReasoningRejected, 1),
+                        (ErrorClass::RateLimited, 2),
+                        (ErrorClass::Stream, 1),
+                    ]),
+                ),
+            ]
+        );
+        // Outcomes are not turns.
+        assert_eq!(entry.total_turns, 0);
+    }
+}
@@ -0,0 +1,297 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::read_config_table;
+use crate::TurnUsage;
+
+/// Per-token prices for one model, using the field names from LiteLLM's
+/// `model_prices_and_context_window.json`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ModelPricing {
+    #[serde(default)]
+    pub input_cost_per_token: Option<f64>,
+    #[serde(default)]
+    pub output_cost_per_token: Option<f64>,
+    #[serde(default)]
+    pub output_cost_per_reasoning_token: Option<f64>,
+    #[serde(default)]
+    pub cache_read_input_token_cost: Option<f64>,
+    #[serde(default)]
+    pub cache_creation_input_token_cost: Option<f64>,
+}
+
+impl ModelPricing {
+    fn is_empty(&self) -> bool {
+        self.input_cost_per_token.is_none()
+            && self.output_cost_per_token.is_none()
+            && self.output_cost_per_reasoning_token.is_none()
+            && self.cache_read_input_token_cost.is_none()
+            && self.cache_creation_input_token_cost.is_none()
+    }
+
+    /// Reasoning tokens are a subset of completion tokens on `/responses`, so
+    /// they are only billed separately when the model has a reasoning rate.
+    /// Likewise cached and cache-creation tokens are carved out of the prompt
+    /// and fall back to the plain input rate when no cache rate is listed.
+    pub fn cost(&self, usage: &TurnUsage) -> f64 {
+        let input_rate = self.input_cost_per_token.unwrap_or(0.0);
+        let output_rate = self.output_cost_per_token.unwrap_or(0.0);
+        let cache_read_rate = self.cache_read_input_token_cost.unwrap_or(input_rate);
+        let cache_creation_rate = self.cache_creation_input_token_cost.unwrap_or(input_rate);
+
+        let prompt_tokens = usage.prompt_tokens.max(0);
+        let cached = usage.cached_tokens.clamp(0, prompt_tokens);
+        let cache_creation = usage.cache_creation_tokens.clamp(0, prompt_tokens - cached);
+        let uncached = prompt_tokens - cached - cache_creation;
+
+        let completion_tokens = usage.completion_tokens.max(0);
+        let (billed_output, billed_reasoning, reasoning_rate) =
+            match self.output_cost_per_reasoning_token {
+                Some(rate) => {
+                    let reasoning = usage.reasoning_tokens.clamp(0, completion_tokens);
+                    (completion_tokens - reasoning, reasoning, rate)
+                }
+                None => (completion_tokens, 0, 0.0),
+            };
+
+        uncached as f64 * input_rate
+            + cached as f64 * cache_read_rate
+            + cache_creation as f64 * cache_creation_rate
+            + billed_output as f64 * output_rate
+            + billed_reasoning as f64 * reasoning_rate
+    }
+}
+
+/// `[telemetry.pricing]` in `config.toml`:
+///
+/// ```toml
+/// [telemetry.pricing]
+/// path = "/home/me/.codex/model_prices_and_context_window.json"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PricingConfig {
+    pub path: PathBuf,
+}
+
+impl PricingConfig {
+    pub fn from_config_toml(path: &Path) -> io::Result<Option<Self>> {
+        read_config_table(path, &["telemetry", "pricing"])
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PricingTable {
+    models: HashMap<String, ModelPricing>,
+}
+
+impl PricingTable {
+    /// Reads a pricing file. `.toml` files are parsed as TOML; anything else
+    /// is treated as JSON.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let is_toml = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+        if is_toml {
+            let value: toml::Value = toml::from_str(&contents)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let value = serde_json::to_value(value)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            Ok(Self::from_value(&value))
+        } else {
+            Self::from_json_str(&contents)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        }
+    }
+
+    /// Accepts either LiteLLM's `model_prices_and_context_window.json` (an
+    /// object keyed by model) or a gateway `/model/info` response.
+    pub fn from_json_str(contents: &str) -> serde_json::Result<Self> {
+        let value: Value = serde_json::from_str(contents)?;
+        Ok(Self::from_value(&value))
+    }
+
+    fn from_value(value: &Value) -> Self {
+        let mut table = Self::default();
+        if let Some(entries) = value.get("data").and_then(Value::as_array) {
+            for entry in entries {
+                let Some(info) = entry.get("model_info") else {
+                    continue;
+                };
+                let Some(pricing) = parse_pricing(info) else {
+                    continue;
+                };
+                if let Some(name) = entry.get("model_name").and_then(Value::as_str) {
+                    table.insert(name, pricing.clone());
+                }
+                if let Some(name) = entry
+                    .get("litellm_params")
+                    .and_then(|params| params.get("model"))
+                    .and_then(Value::as_str)
+                {
+                    table
+                        .models
+                        .entry(normalize_model_key(name))
+                        .or_insert(pricing);
+                }
+            }
+        } else if let Some(entries) = value.as_object() {
+            for (name, entry) in entries {
+                // Documentation entry in LiteLLM's file, not a model.
+                if name == "sample_spec" {
+                    continue;
+                }
+                if let Some(pricing) = parse_pricing(entry) {
+                    table.insert(name, pricing);
+                }
+            }
+        }
+        table
+    }
+
+    pub fn insert(&mut self, model: &str, pricing: ModelPricing) {
+        self.models.insert(normalize_model_key(model), pricing);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// Looks up a model by exact slug, then with provider prefixes stripped
+    /// one segment at a time (`vercel/openai/gpt-5` → `openai/gpt-5` → `gpt-5`).
+    pub fn lookup(&self, model: &str) -> Option<&ModelPricing> {
+        let mut key = normalize_model_key(model);
+        loop {
+            if let Some(pricing) = self.models.get(&key) {
+                return Some(pricing);
+            }
+            let (_, rest) = key.split_once('/')?;
+            key = rest.to_string();
+        }
+    }
+
+    pub fn cost(&self, model: &str, usage: &TurnUsage) -> Option<f64> {
+        self.lookup(model).map(|pricing| pricing.cost(usage))
+    }
+}
+
+fn normalize_model_key(model: &str) -> String {
+    model.trim().to_ascii_lowercase()
+}
+
+fn parse_pricing(value: &Value) -> Option<ModelPricing> {
+    let pricing: ModelPricing = serde_json::from_value(value.clone()).ok()?;
+    (!pricing.is_empty()).then_some(pricing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn usage(prompt: i64, cached: i64, cache_creation: i64, completion: i64) -> TurnUsage {
+        TurnUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            reasoning_tokens: 0,
+            cached_tokens: cached,
+            cache_creation_tokens: cache_creation,
+            total_tokens: prompt + completion,
+        }
+    }
+
+    #[test]
+    fn bills_cache_reads_and_writes_at_their_own_rates() {
+        let table = PricingTable::from_json_str(
+            r#"{
+                "sample_spec": { "input_cost_per_token": 1.0 },
+                "claude-sonnet-4": {
+                    "input_cost_per_token": 3e-6,
+                    "output_cost_per_token": 15e-6,
+                    "cache_read_input_token_cost": 0.3e-6,
+                    "cache_creation_input_token_cost": 3.75e-6
+                },
+                "gpt-5": { "input_cost_per_token": 1e-6, "output_cost_per_token": 8e-6 }
+            }"#,
+        )
+        .expect("pricing json");
+
+        // 1000 prompt tokens: 600 uncached, 300 cache reads, 100 cache writes.
+        let cost = table
+            .cost(
+                "vercel/anthropic/claude-sonnet-4",
+                &usage(1_000, 300, 100, 200),
+            )
+            .expect("priced");
+        let expected = 600.0 * 3e-6 + 300.0 * 0.3e-6 + 100.0 * 3.75e-6 + 200.0 * 15e-6;
+        assert!((cost - expected).abs() < 1e-12, "{cost} != {expected}");
+
+        // Without cache rates, cached tokens fall back to the input rate.
+        let cost = table
+            .cost("GPT-5", &usage(1_000, 400, 0, 100))
+            .expect("priced");
+        assert!((cost - (1_000.0 * 1e-6 + 100.0 * 8e-6)).abs() < 1e-12);
+
+        // Cache counts larger than the prompt are clamped rather than billed twice.
+        let cost = table
+            .cost("claude-sonnet-4", &usage(100, 80, 80, 0))
+            .expect("priced");
+        assert!((cost - (80.0 * 0.3e-6 + 20.0 * 3.75e-6)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reasoning_tokens_are_billed_separately_only_with_a_reasoning_rate() {
+        let pricing = ModelPricing {
+            output_cost_per_token: Some(2.0),
+            output_cost_per_reasoning_token: Some(5.0),
+            ..ModelPricing::default()
+        };
+        let turn = TurnUsage {
+            completion_tokens: 10,
+            reasoning_tokens: 4,
+            ..TurnUsage::default()
+        };
+        assert_eq!(pricing.cost(&turn), 6.0 * 2.0 + 4.0 * 5.0);
+
+        let pricing = ModelPricing {
+            output_cost_per_reasoning_token: None,
+            ..pricing
+        };
+        assert_eq!(pricing.cost(&turn), 10.0 * 2.0);
+    }
+
+    #[test]
+    fn unknown_models_have_no_price() {
+        let table = PricingTable::from_json_str(
+            r#"{
+                "data": [{
+                    "model_name": "team-fast",
+                    "litellm_params": { "model": "openai/gpt-5-mini" },
+                    "model_info": { "input_cost_per_token": 1e-7 }
+                }, {
+                    "model_name": "unpriced",
+                    "model_info": { "max_tokens": 8192 }
+                }]
+            }"#,
+        )
+        .expect("model info json");
+
+        assert!(table.lookup("team-fast").is_some());
+        assert!(table.lookup("vercel/openai/gpt-5-mini").is_some());
+        assert_eq!(table.lookup("unpriced"), None);
+        assert_eq!(table.cost("gpt-5", &usage(10, 0, 0, 10)), None);
+        assert_eq!(
+            PricingTable::default().cost("team-fast", &usage(10, 0, 0, 0)),
+            None
+        );
+    }
+}
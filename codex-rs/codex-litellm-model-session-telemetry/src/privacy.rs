@@ -0,0 +1,425 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::{DateTime, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use crate::config::read_config_table;
+use crate::record::{LogRecord, RollupRecord};
+use crate::{add_cost, DEFAULT_SESSION_KEY};
+
+/// Record fields that identify a session or a project.
+const IDENTIFYING_FIELDS: [&str; 5] = ["session_id", "cwd", "git_remote", "repo", "branch"];
+
+fn default_rollup_interval_secs() -> u64 {
+    3600
+}
+
+static PRIVACY: Lazy<Mutex<Option<Privacy>>> = Lazy::new(|| Mutex::new(None));
+
+/// How much of each record leaves the process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivacyMode {
+    /// Records are written as recorded.
+    #[default]
+    Full,
+    /// Session ids, working directories, remotes, repository and branch
+    /// names are replaced by salted hashes.
+    Hashed,
+    /// No per-turn records; one rollup line per model per interval.
+    #[serde(alias = "aggregate_only")]
+    Aggregate,
+}
+
+/// Privacy level applied to every sink, read from `[telemetry.privacy]` in
+/// `config.toml`:
+///
+/// ```toml
+/// [telemetry.privacy]
+/// mode = "hashed"
+/// salt = "rotate-me-per-fleet"
+/// rollup_interval_secs = 3600
+/// ```
+///
+/// `salt` is required unless `mode` is `full`. `rollup_interval_secs` only
+/// applies to `aggregate`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PrivacyConfig {
+    #[serde(default)]
+    pub mode: PrivacyMode,
+    #[serde(default)]
+    pub salt: Option<String>,
+    #[serde(default = "default_rollup_interval_secs")]
+    pub rollup_interval_secs: u64,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            mode: PrivacyMode::Full,
+            salt: None,
+            rollup_interval_secs: default_rollup_interval_secs(),
+        }
+    }
+}
+
+impl PrivacyConfig {
+    pub fn from_config_toml(path: &Path) -> io::Result<Option<Self>> {
+        read_config_table(path, &["telemetry", "privacy"])
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        let invalid = |message: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        if self.mode != PrivacyMode::Full && self.salt.as_deref().is_none_or(str::is_empty) {
+            return invalid("telemetry privacy modes other than `full` need a `salt`");
+        }
+        if self.rollup_interval_secs == 0 {
+            return invalid("telemetry `rollup_interval_secs` must be positive");
+        }
+        Ok(())
+    }
+
+    fn hash(&self, value: &str) -> String {
+        salted_hash(self.salt.as_deref().unwrap_or_default(), value)
+    }
+}
+
+struct Privacy {
+    config: PrivacyConfig,
+    rollups: Rollups,
+}
+
+/// Installs the privacy level for records queued from now on and returns
+/// the rollup lines still pending under the previous one.
+pub(crate) fn configure(config: Option<PrivacyConfig>) -> io::Result<Vec<Value>> {
+    if let Some(config) = &config {
+        config.validate()?;
+    }
+    let previous = std::mem::replace(
+        &mut *PRIVACY.lock(),
+        config.map(|config| Privacy {
+            config,
+            rollups: Rollups::default(),
+        }),
+    );
+    Ok(previous.map_or_else(Vec::new, |mut privacy| privacy.rollups.take()))
+}
+
+/// Turns a turn or request-outcome record into what the sinks may see: the
+/// record itself, a redacted copy, or, in aggregate mode, any rollup lines
+/// completed by its arrival.
+pub(crate) fn apply(record: Value) -> Vec<Value> {
+    let mut guard = PRIVACY.lock();
+    let Some(privacy) = guard.as_mut() else {
+        return vec![record];
+    };
+    match privacy.config.mode {
+        PrivacyMode::Full => vec![record],
+        PrivacyMode::Hashed => vec![hash_identifiers(record, &privacy.config)],
+        PrivacyMode::Aggregate => {
+            privacy
+                .rollups
+                .add(&record, Utc::now(), privacy.config.rollup_interval_secs)
+        }
+    }
+}
+
+/// Rollup lines for the interval in progress, for writing before exit.
+pub(crate) fn take_rollups() -> Vec<Value> {
+    PRIVACY
+        .lock()
+        .as_mut()
+        .map_or_else(Vec::new, |privacy| privacy.rollups.take())
+}
+
+/// When the open aggregate interval ends, if one is open.
+pub(crate) fn rollup_due_at() -> Option<DateTime<Utc>> {
+    PRIVACY
+        .lock()
+        .as_ref()
+        .and_then(|privacy| privacy.rollups.period)
+        .map(|(_, end)| end)
+}
+
+/// Rollup lines for an interval that ended before `now`. The writer calls
+/// this when idle, so a window is written even if no record follows it.
+pub(crate) fn take_elapsed_rollups(now: DateTime<Utc>) -> Vec<Value> {
+    match PRIVACY.lock().as_mut() {
+        Some(privacy) if privacy.rollups.period.is_some_and(|(_, end)| end <= now) => {
+            privacy.rollups.take()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Whether sinks receive only rollups.
+pub(crate) fn is_aggregate() -> bool {
+    PRIVACY
+        .lock()
+        .as_ref()
+        .is_some_and(|privacy| privacy.config.mode == PrivacyMode::Aggregate)
+}
+
+/// The session id as written to sinks and metrics labels.
+pub(crate) fn session_label(key: &str) -> String {
+    match PRIVACY.lock().as_ref() {
+        Some(privacy) if privacy.config.mode != PrivacyMode::Full && key != DEFAULT_SESSION_KEY => {
+            privacy.config.hash(key)
+        }
+        _ => key.to_string(),
+    }
+}
+
+/// The session id to attach to a turn's OTLP span, or `None` when per-turn
+/// spans are withheld.
+pub(crate) fn span_session_id(session_id: Option<&str>) -> Option<Option<String>> {
+    match PRIVACY.lock().as_ref().map(|privacy| &privacy.config) {
+        Some(config) if config.mode == PrivacyMode::Aggregate => None,
+        Some(config) if config.mode == PrivacyMode::Hashed => {
+            Some(session_id.map(|session_id| config.hash(session_id)))
+        }
+        _ => Some(session_id.map(str::to_string)),
+    }
+}
+
+/// First 16 hex digits of SHA-256 over the salt and value. Stable for a
+/// salt, so hashed sessions and repositories still group together.
+fn salted_hash(salt: &str, value: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(salt.as_bytes())
+        .chain_update([0])
+        .chain_update(value.as_bytes())
+        .finalize();
+    digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Hashes `IDENTIFYING_FIELDS` and every tag value. Tag keys are kept so
+/// usage still groups by tag.
+fn hash_identifiers(mut record: Value, config: &PrivacyConfig) -> Value {
+    if let Some(fields) = record.as_object_mut() {
+        for field in IDENTIFYING_FIELDS {
+            if let Some(Value::String(value)) = fields.get_mut(field) {
+                *value = config.hash(value);
+            }
+        }
+        if let Some(Value::Object(tags)) = fields.get_mut("tags") {
+            for value in tags.values_mut() {
+                if let Value::String(value) = value {
+                    *value = config.hash(value);
+                }
+            }
+        }
+    }
+    record
+}
+
+/// Per-model totals for the interval in progress.
+#[derive(Debug, Default)]
+struct Rollups {
+    period: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    models: BTreeMap<String, RollupRecord>,
+}
+
+impl Rollups {
+    /// Adds `record` to the interval containing `now`, first returning the
+    /// lines for an earlier interval if one was still open.
+    fn add(&mut self, record: &Value, now: DateTime<Utc>, interval_secs: u64) -> Vec<Value> {
+        let Ok(Some(record)) = LogRecord::from_value(record.clone()) else {
+            return Vec::new();
+        };
+        if matches!(record, LogRecord::Rollup(_)) {
+            return Vec::new();
+        }
+        let period = interval_containing(now, interval_secs);
+        let completed = if self.period.is_some_and(|open| open != period) {
+            self.take()
+        } else {
+            Vec::new()
+        };
+        self.period = Some(period);
+
+        match record {
+            LogRecord::Outcome(record) => {
+                let entry = self.models.entry(record.model).or_default();
+                entry.failed_requests += u32::from(!record.outcome.succeeded);
+                entry.reasoning_retries += u32::from(record.outcome.retried_without_reasoning);
+            }
+            LogRecord::Rollup(_) => {}
+            LogRecord::Turn(record) => {
+                let entry = self.models.entry(record.model).or_default();
+                entry.turns += 1;
+                entry.prompt_tokens += record.prompt_tokens;
+                entry.completion_tokens += record.completion_tokens;
+                entry.reasoning_tokens += record.reasoning_tokens;
+                entry.cached_tokens += record.cached_tokens;
+                entry.cache_creation_tokens += record.cache_creation_tokens;
+                entry.total_tokens += record.total_tokens;
+                entry.cost_usd = add_cost(entry.cost_usd, record.cost_usd);
+            }
+        }
+        completed
+    }
+
+    /// One line per model for the open interval, which is then closed.
+    fn take(&mut self) -> Vec<Value> {
+        let Some((start, end)) = self.period.take() else {
+            return Vec::new();
+        };
+        std::mem::take(&mut self.models)
+            .into_iter()
+            .map(|(model, entry)| {
+                RollupRecord {
+                    ts: start,
+                    period_end: end,
+                    model,
+                    ..entry
+                }
+                .to_value()
+            })
+            .collect()
+    }
+}
+
+/// The `interval_secs`-long window, aligned to the Unix epoch, holding `now`.
+fn interval_containing(now: DateTime<Utc>, interval_secs: u64) -> (DateTime<Utc>, DateTime<Utc>) {
+    let interval = i64::try_from(interval_secs).unwrap_or(i64::MAX);
+    let start = now.timestamp().div_euclid(interval) * interval;
+    let at = |secs: i64| Utc.timestamp_opt(secs, 0).single().unwrap_or(now);
+    (at(start), at(start.saturating_add(interval)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn hashed(salt: &str) -> PrivacyConfig {
+        PrivacyConfig {
+            mode: PrivacyMode::Hashed,
+            salt: Some(salt.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hashed_mode_replaces_identifiers_with_stable_salted_hashes() {
+        let record = json!({
+            "session_id": "sess-1",
+            "model": "gpt-5",
+            "cwd": "/home/dev/widgets",
+            "git_remote": "https://github.com/acme/widgets.git",
+            "repo": "acme/widgets",
+            "branch": "feature/billing",
+            "branch_note": "feature/billing",
+            "tags": { "ticket": "ENG-123" },
+            "total_tokens": 42,
+        });
+
+        let first = hash_identifiers(record.clone(), &hashed("fleet-a"));
+        let again = hash_identifiers(record.clone(), &hashed("fleet-a"));
+        let other_salt = hash_identifiers(record, &hashed("fleet-b"));
+
+        assert_eq!(first, again);
+        assert_ne!(first["repo"], other_salt["repo"]);
+        for field in IDENTIFYING_FIELDS {
+            let value = first[field].as_str().expect(field);
+            assert_eq!(value.len(), 16, "{field}");
+            assert!(!value.contains("widgets") && !value.contains("sess"));
+        }
+        assert_eq!(first["model"], "gpt-5");
+        assert_eq!(first["tags"]["ticket"], hashed("fleet-a").hash("ENG-123"));
+        assert_eq!(first["branch_note"], "feature/billing");
+        assert_eq!(first["total_tokens"], 42);
+
+        assert!(PrivacyConfig {
+            mode: PrivacyMode::Hashed,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn aggregate_mode_emits_per_model_rollups_when_an_interval_closes() {
+        let at = |rfc3339: &str| {
+            DateTime::parse_from_rfc3339(rfc3339)
+                .expect("timestamp")
+                .with_timezone(&Utc)
+        };
+        let turn = |model: &str, prompt: i64, cost: f64| {
+            json!({
+                "ts": "2025-03-01T10:00:00.000Z",
+                "session_id": "sess-1",
+                "model": model,
+                "repo": "acme/widgets",
+                "prompt_tokens": prompt,
+                "completion_tokens": 10,
+                "total_tokens": prompt + 10,
+                "cost_usd": cost,
+            })
+        };
+        let failure = json!({
+            "event": "request_outcome",
+            "ts": "2025-03-01T10:30:00.000Z",
+            "session_id": "sess-1",
+            "model": "gpt-5",
+            "retried_without_reasoning": true,
+            "succeeded": false,
+        });
+
+        let mut rollups = Rollups::default();
+        let mut written = Vec::new();
+        written.extend(rollups.add(&turn("gpt-5", 100, 0.25), at("2025-03-01T10:05:00Z"), 3600));
+        written.extend(rollups.add(&turn("gpt-5", 50, 0.5), at("2025-03-01T10:40:00Z"), 3600));
+        written.extend(rollups.add(&failure, at("2025-03-01T10:59:59Z"), 3600));
+        assert_eq!(written, Vec::<Value>::new());
+
+        written.extend(rollups.add(&turn("m2", 7, 0.0), at("2025-03-01T11:00:00Z"), 3600));
+        assert_eq!(
+            written,
+            vec![json!({
+                "event": "rollup",
+                "ts": "2025-03-01T10:00:00.000Z",
+                "period_end": "2025-03-01T11:00:00.000Z",
+                "model": "gpt-5",
+                "turns": 2,
+                "prompt_tokens": 150,
+                "completion_tokens": 20,
+                "reasoning_tokens": 0,
+                "cached_tokens": 0,
+                "cache_creation_tokens": 0,
+                "total_tokens": 170,
+                "cost_usd": 0.75,
+                "failed_requests": 1,
+                "reasoning_retries": 1,
+            })]
+        );
+
+        // A rollup line fed back in, e.g. by a replayed log, is not counted.
+        let rollup = written[0].clone();
+        assert_eq!(
+            rollups.add(&rollup, at("2025-03-01T11:30:00Z"), 3600),
+            Vec::<Value>::new()
+        );
+
+        let pending = rollups.take();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0]["model"], "m2");
+        assert_eq!(pending[0]["ts"], "2025-03-01T11:00:00.000Z");
+        assert!(rollups.take().is_empty());
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::record::{read_log_records, LogRecord};
+use crate::{add_cost, normalize_session_id};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UsageGroupBy {
+    /// UTC day of the turn.
+    #[default]
+    Day,
+    Model,
+    ReasoningEffort,
+    /// `owner/name` from the session's git remote; see `WorkspaceInfo`.
+    Repo,
+    Session,
+}
+
+impl UsageGroupBy {
+    /// Aggregate-mode rollups only know their window and model, so they
+    /// group under `-` by effort, repo and session.
+    fn key(self, record: &LogRecord) -> String {
+        let turn = match record {
+            LogRecord::Turn(turn) => Some(turn),
+            _ => None,
+        };
+        match self {
+            UsageGroupBy::Day => record.ts().date_naive().to_string(),
+            UsageGroupBy::Model => record.model().to_string(),
+            UsageGroupBy::ReasoningEffort => turn
+                .and_then(|turn| turn.reasoning_effort.clone())
+                .unwrap_or_else(|| "-".to_string()),
+            UsageGroupBy::Repo => turn
+                .and_then(|turn| turn.repo.clone())
+                .unwrap_or_else(|| "-".to_string()),
+            UsageGroupBy::Session => turn
+                .map(|turn| normalize_session_id(turn.session_id.as_deref()))
+                .unwrap_or_else(|| "-".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for UsageGroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UsageGroupBy::Day => "day",
+            UsageGroupBy::Model => "model",
+            UsageGroupBy::ReasoningEffort => "effort",
+            UsageGroupBy::Repo => "repo",
+            UsageGroupBy::Session => "session",
+        })
+    }
+}
+
+impl FromStr for UsageGroupBy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "day" => Ok(UsageGroupBy::Day),
+            "model" => Ok(UsageGroupBy::Model),
+            "effort" | "reasoning_effort" | "reasoning-effort" => Ok(UsageGroupBy::ReasoningEffort),
+            "repo" => Ok(UsageGroupBy::Repo),
+            "session" => Ok(UsageGroupBy::Session),
+            other => Err(format!(
+                "unknown grouping `{other}`; expected day, model, effort, repo or session"
+            )),
+        }
+    }
+}
+
+/// Which turns to read and how to group them. `since` is inclusive and
+/// `until` exclusive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageQuery {
+    pub group_by: UsageGroupBy,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl UsageQuery {
+    /// Builds a query from command-line bounds. Each bound is an RFC 3339
+    /// timestamp or a `YYYY-MM-DD` UTC date; a date given as `until` covers
+    /// that whole day.
+    pub fn from_bounds(
+        group_by: UsageGroupBy,
+        since: Option<&str>,
+        until: Option<&str>,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            group_by,
+            since: since.map(|value| parse_bound(value, false)).transpose()?,
+            until: until.map(|value| parse_bound(value, true)).transpose()?,
+        })
+    }
+
+    fn matches(&self, record: &LogRecord) -> bool {
+        !matches!(record, LogRecord::Outcome(_)) && in_window(record.ts(), self.since, self.until)
+    }
+}
+
+/// Whether `ts` falls in `[since, until)`. Unbounded on a missing side.
+pub(crate) fn in_window(
+    ts: DateTime<Utc>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> bool {
+    since.is_none_or(|since| ts >= since) && until.is_none_or(|until| ts < until)
+}
+
+pub(crate) fn parse_bound(value: &str, end_of_day: bool) -> io::Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid time `{value}`; expected YYYY-MM-DD or an RFC 3339 timestamp"),
+        )
+    })?;
+    let date = if end_of_day {
+        date.succ_opt().unwrap_or(date)
+    } else {
+        date
+    };
+    Ok(date.and_time(NaiveTime::MIN).and_utc())
+}
+
+/// Usage summed over every turn sharing one grouping key.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageRollup {
+    pub key: String,
+    pub turns: u64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub reasoning_tokens: i64,
+    pub cached_tokens: i64,
+    pub cache_creation_tokens: i64,
+    pub total_tokens: i64,
+    pub cost_usd: Option<f64>,
+    pub first_turn_at: Option<DateTime<Utc>>,
+    pub last_turn_at: Option<DateTime<Utc>>,
+}
+
+/// Reads the telemetry log at `path`, including rotated segments, and rolls
+/// matching turns up by the requested key. Rollup lines from aggregate
+/// privacy mode count by the start of their window. Days come back in date
+/// order; other groupings put the heaviest users first.
+pub fn query_usage(path: &Path, query: &UsageQuery) -> io::Result<Vec<UsageRollup>> {
+    let records = read_log_records(path, |record| query.matches(record))?;
+    let mut rollups: BTreeMap<String, UsageRollup> = BTreeMap::new();
+    for record in records {
+        let (turns, usage, cost_usd) = match &record {
+            LogRecord::Turn(turn) => (1, turn.usage(), turn.cost_usd),
+            LogRecord::Rollup(rollup) => (u64::from(rollup.turns), rollup.usage(), rollup.cost_usd),
+            LogRecord::Outcome(_) => continue,
+        };
+        let key = query.group_by.key(&record);
+        let rollup = rollups.entry(key.clone()).or_insert_with(|| UsageRollup {
+            key,
+            ..Default::default()
+        });
+        rollup.turns += turns;
+        rollup.prompt_tokens += usage.prompt_tokens;
+        rollup.completion_tokens += usage.completion_tokens;
+        rollup.reasoning_tokens += usage.reasoning_tokens;
+        rollup.cached_tokens += usage.cached_tokens;
+        rollup.cache_creation_tokens += usage.cache_creation_tokens;
+        rollup.total_tokens += usage.total_tokens;
+        rollup.cost_usd = add_cost(rollup.cost_usd, cost_usd);
+        let ts = record.ts();
+        rollup.first_turn_at = Some(rollup.first_turn_at.map_or(ts, |first| first.min(ts)));
+        rollup.last_turn_at = Some(rollup.last_turn_at.map_or(ts, |last| last.max(ts)));
+    }
+
+    let mut rollups: Vec<UsageRollup> = rollups.into_values().collect();
+    if query.group_by != UsageGroupBy::Day {
+        rollups.sort_by_key(|rollup| Reverse(rollup.total_tokens));
+    }
+    Ok(rollups)
+}
+
+/// Renders rollups as a fixed-width table with a trailing total row.
+pub fn render_usage_table(group_by: UsageGroupBy, rollups: &[UsageRollup]) -> String {
+    let mut total = UsageRollup {
+        key: "total".to_string(),
+        ..Default::default()
+    };
+    for rollup in rollups {
+        total.turns += rollup.turns;
+        total.prompt_tokens += rollup.prompt_tokens;
+        total.completion_tokens += rollup.completion_tokens;
+        total.reasoning_tokens += rollup.reasoning_tokens;
+        total.cached_tokens += rollup.cached_tokens;
+        total.total_tokens += rollup.total_tokens;
+        total.cost_usd = add_cost(total.cost_usd, rollup.cost_usd);
+    }
+
+    let header = vec![
+        group_by.to_string(),
+        "turns".to_string(),
+        "prompt".to_string(),
+        "completion".to_string(),
+        "reasoning".to_string(),
+        "cached".to_string(),
+        "total".to_string(),
+        "cost_usd".to_string(),
+    ];
+    let row = |rollup: &UsageRollup| {
+        vec![
+            rollup.key.clone(),
+            rollup.turns.to_string(),
+            rollup.prompt_tokens.to_string(),
+            rollup.completion_tokens.to_string(),
+            rollup.reasoning_tokens.to_string(),
+            rollup.cached_tokens.to_string(),
+            rollup.total_tokens.to_string(),
+            rollup
+                .cost_usd
+                .map(|cost| format!("{cost:.4}"))
+                .unwrap_or_else(|| "-".to_string()),
+        ]
+    };
+    let mut rows = vec![header];
+    rows.extend(rollups.iter().map(row));
+    rows.push(row(&total));
+    format_table(&rows)
+}
+
+/// Aligns `rows` into columns: the first left-aligned, the rest
+/// right-aligned. The first row is the header.
+pub(crate) fn format_table(rows: &[Vec<String>]) -> String {
+    let mut widths = vec![0usize; rows.first().map_or(0, Vec::len)];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (index, (cell, &width)) in row.iter().zip(&widths).enumerate() {
+            if index == 0 {
+                let _ = write!(line, "{cell:<width$}");
+            } else {
+                let _ = write!(line, "  {cell:>width$}");
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const LOG: &str = r#"{"ts":"2025-03-01T10:00:00.000Z","session_id":"a","model":"m1","reasoning_effort":"high","repo":"acme/widgets","prompt_tokens":100,"completion_tokens":10,"total_tokens":110,"cost_usd":0.5}
+{"ts":"2025-03-01T23:59:00.000Z","session_id":"b","model":"m2","prompt_tokens":40,"completion_tokens":4,"total_tokens":44}
+{"ts":"2025-03-02T08:00:00.000Z","session_id":"a","model":"m1","reasoning_effort":"low","prompt_tokens":300,"completion_tokens":30,"total_tokens":330,"cost_usd":1.0}
+not json
+"#;
+
+    #[test]
+    fn rolls_up_turns_by_key_within_the_window() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        std::fs::write(&path, LOG).expect("write log");
+
+        let by_day = query_usage(&path, &UsageQuery::default()).expect("query by day");
+        assert_eq!(
+            by_day
+                .iter()
+                .map(|rollup| (rollup.key.as_str(), rollup.turns, rollup.total_tokens))
+                .collect::<Vec<_>>(),
+            vec![("2025-03-01", 2, 154), ("2025-03-02", 1, 330)]
+        );
+        assert_eq!(by_day[0].cost_usd, Some(0.5));
+
+        let query = UsageQuery::from_bounds(UsageGroupBy::Model, None, Some("2025-03-01"))
+            .expect("parse bounds");
+        let by_model = query_usage(&path, &query).expect("query by model");
+        assert_eq!(
+            by_model
+                .iter()
+                .map(|rollup| (rollup.key.as_str(), rollup.total_tokens))
+                .collect::<Vec<_>>(),
+            vec![("m1", 110), ("m2", 44)]
+        );
+
+        let query = UsageQuery::from_bounds(
+            UsageGroupBy::ReasoningEffort,
+            Some("2025-03-02T00:00:00Z"),
+            None,
+        )
+        .expect("parse bounds");
+        let by_effort = query_usage(&path, &query).expect("query by effort");
+        assert_eq!(by_effort.len(), 1);
+        assert_eq!(by_effort[0].key, "low");
+        assert_eq!(by_effort[0].cost_usd, Some(1.0));
+
+        let query = UsageQuery {
+            group_by: UsageGroupBy::Repo,
+            ..Default::default()
+        };
+        let by_repo = query_usage(&path, &query).expect("query by repo");
+        assert_eq!(
+            by_repo
+                .iter()
+                .map(|rollup| (rollup.key.as_str(), rollup.total_tokens))
+                .collect::<Vec<_>>(),
+            vec![("-", 374), ("acme/widgets", 110)]
+        );
+    }
+
+    #[test]
+    fn aggregate_rollups_count_by_window_start_and_model() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        let rollup = r#"{"event":"rollup","ts":"2025-03-02T09:00:00.000Z","period_end":"2025-03-02T10:00:00.000Z","model":"m2","turns":4,"prompt_tokens":80,"completion_tokens":20,"total_tokens":100,"cost_usd":0.25,"failed_requests":1}"#;
+        std::fs::write(&path, format!("{LOG}{rollup}\n")).expect("write log");
+
+        let by_day = query_usage(&path, &UsageQuery::default()).expect("query by day");
+        assert_eq!(
+            by_day
+                .iter()
+                .map(|rollup| (rollup.key.as_str(), rollup.turns, rollup.total_tokens))
+                .collect::<Vec<_>>(),
+            vec![("2025-03-01", 2, 154), ("2025-03-02", 5, 430)]
+        );
+        assert_eq!(by_day[1].cost_usd, Some(1.25));
+
+        let query = UsageQuery {
+            group_by: UsageGroupBy::Session,
+            ..Default::default()
+        };
+        let by_session = query_usage(&path, &query).expect("query by session");
+        assert_eq!(
+            by_session
+                .iter()
+                .map(|rollup| (rollup.key.as_str(), rollup.turns))
+                .collect::<Vec<_>>(),
+            vec![("a", 2), ("-", 4), ("b", 1)]
+        );
+    }
+
+    #[test]
+    fn table_ends_with_a_total_row() {
+        let rollups = vec![UsageRollup {
+            key: "m1".to_string(),
+            turns: 2,
+            total_tokens: 440,
+            cost_usd: Some(1.5),
+            ..Default::default()
+        }];
+
+        let table = render_usage_table(UsageGroupBy::Model, &rollups);
+
+        assert_eq!(
+            table,
+            "model  turns  prompt  completion  reasoning  cached  total  cost_usd\n\
+             m1         2       0           0          0       0    440    1.5000\n\
+             total      2       0           0          0       0    440    1.5000\n"
+        );
+    }
+}
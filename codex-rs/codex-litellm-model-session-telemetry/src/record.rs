@@ -0,0 +1,413 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    for_each_log_line, RequestOutcome, SessionTags, TurnMetrics, TurnUsage, WorkspaceInfo,
+    REQUEST_OUTCOME_EVENT, ROLLUP_EVENT,
+};
+
+/// Version written in `schema_version` of new turn records. Bump it whenever
+/// a field is added, renamed or changes meaning.
+///
+/// - 0: lines written before versioning, without `schema_version`. Any
+///   field added since the first release (cache tokens, cost, timing,
+///   workspace, tags, the gateway request id) may be missing.
+/// - 1: the fields of `TurnRecord`.
+pub const TURN_RECORD_SCHEMA_VERSION: u32 = 1;
+
+/// One turn as written to the JSONL log and handed to every sink. Fields a
+/// line does not carry read as `None`, zero or empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnRecord {
+    /// Version the line was written with; see `TURN_RECORD_SCHEMA_VERSION`.
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(with = "millis")]
+    pub ts: DateTime<Utc>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    pub model: String,
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+    #[serde(default)]
+    pub gateway_request_id: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub git_remote: Option<String>,
+    #[serde(default)]
+    pub repo: Option<String>,
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub tags: SessionTags,
+    #[serde(default)]
+    pub prompt_tokens: i64,
+    #[serde(default)]
+    pub completion_tokens: i64,
+    #[serde(default)]
+    pub reasoning_tokens: i64,
+    #[serde(default)]
+    pub cached_tokens: i64,
+    #[serde(default)]
+    pub cache_creation_tokens: i64,
+    #[serde(default)]
+    pub total_tokens: i64,
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+    #[serde(default, with = "optional_millis")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "optional_millis")]
+    pub first_token_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "optional_millis")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub ttft_ms: Option<i64>,
+    #[serde(default)]
+    pub duration_ms: Option<i64>,
+    #[serde(default)]
+    pub output_tokens_per_sec: Option<f64>,
+}
+
+impl TurnRecord {
+    /// Parses one log line. Request outcomes, rollups and other events yield
+    /// `None`; so does a blank line.
+    pub fn from_json_line(line: &str) -> io::Result<Option<Self>> {
+        Ok(match LogRecord::from_json_line(line)? {
+            Some(LogRecord::Turn(record)) => Some(*record),
+            _ => None,
+        })
+    }
+
+    /// The record as one JSON line, without the trailing newline.
+    pub fn to_json_line(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn usage(&self) -> TurnUsage {
+        TurnUsage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+            reasoning_tokens: self.reasoning_tokens,
+            cached_tokens: self.cached_tokens,
+            cache_creation_tokens: self.cache_creation_tokens,
+            total_tokens: self.total_tokens,
+        }
+    }
+
+    /// Turns recorded before `set_session_workspace` have no `cwd`.
+    pub fn workspace(&self) -> Option<WorkspaceInfo> {
+        self.cwd.clone().map(|cwd| WorkspaceInfo {
+            cwd,
+            git_remote: self.git_remote.clone(),
+            repo: self.repo.clone(),
+            branch: self.branch.clone(),
+        })
+    }
+
+    pub(crate) fn metrics(&self) -> TurnMetrics {
+        TurnMetrics {
+            cost_usd: self.cost_usd,
+            ttft_ms: self.ttft_ms,
+            duration_ms: self.duration_ms,
+            output_tokens_per_sec: self.output_tokens_per_sec,
+        }
+    }
+}
+
+/// A `"event": "request_outcome"` line, written by `record_request_outcome`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct OutcomeRecord {
+    #[serde(with = "millis")]
+    pub(crate) ts: DateTime<Utc>,
+    #[serde(default)]
+    pub(crate) session_id: Option<String>,
+    pub(crate) model: String,
+    #[serde(flatten)]
+    pub(crate) outcome: RequestOutcome,
+}
+
+impl OutcomeRecord {
+    /// The record as handed to sinks, tagged with its `event`.
+    pub(crate) fn to_value(&self) -> Value {
+        tagged(self, REQUEST_OUTCOME_EVENT)
+    }
+}
+
+/// A `"event": "rollup"` line: one model's totals for one aggregate-mode
+/// window, with no session, workspace or tags.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RollupRecord {
+    /// Start of the window.
+    #[serde(with = "millis")]
+    pub(crate) ts: DateTime<Utc>,
+    #[serde(with = "millis")]
+    pub(crate) period_end: DateTime<Utc>,
+    pub(crate) model: String,
+    #[serde(default)]
+    pub(crate) turns: u32,
+    #[serde(default)]
+    pub(crate) prompt_tokens: i64,
+    #[serde(default)]
+    pub(crate) completion_tokens: i64,
+    #[serde(default)]
+    pub(crate) reasoning_tokens: i64,
+    #[serde(default)]
+    pub(crate) cached_tokens: i64,
+    #[serde(default)]
+    pub(crate) cache_creation_tokens: i64,
+    #[serde(default)]
+    pub(crate) total_tokens: i64,
+    #[serde(default)]
+    pub(crate) cost_usd: Option<f64>,
+    #[serde(default)]
+    pub(crate) failed_requests: u32,
+    #[serde(default)]
+    pub(crate) reasoning_retries: u32,
+}
+
+impl RollupRecord {
+    pub(crate) fn to_value(&self) -> Value {
+        tagged(self, ROLLUP_EVENT)
+    }
+
+    pub(crate) fn usage(&self) -> TurnUsage {
+        TurnUsage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+            reasoning_tokens: self.reasoning_tokens,
+            cached_tokens: self.cached_tokens,
+            cache_creation_tokens: self.cache_creation_tokens,
+            total_tokens: self.total_tokens,
+        }
+    }
+}
+
+fn tagged(record: &impl Serialize, event: &str) -> Value {
+    let mut value = serde_json::to_value(record).unwrap_or(Value::Null);
+    if let Some(fields) = value.as_object_mut() {
+        fields.insert("event".to_string(), Value::from(event));
+    }
+    value
+}
+
+/// Any line of the telemetry log this build understands.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum LogRecord {
+    Turn(Box<TurnRecord>),
+    Outcome(OutcomeRecord),
+    Rollup(RollupRecord),
+}
+
+impl LogRecord {
+    /// Parses one log line. Unknown events and blank lines yield `None`.
+    pub(crate) fn from_json_line(line: &str) -> io::Result<Option<Self>> {
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        Ok(Self::from_value(serde_json::from_str(line)?)?)
+    }
+
+    /// Parses a record as handed to sinks.
+    pub(crate) fn from_value(value: Value) -> serde_json::Result<Option<Self>> {
+        match value.get("event") {
+            None | Some(Value::Null) => Ok(Some(LogRecord::Turn(serde_json::from_value(value)?))),
+            Some(event) if *event == REQUEST_OUTCOME_EVENT => {
+                Ok(Some(LogRecord::Outcome(serde_json::from_value(value)?)))
+            }
+            Some(event) if *event == ROLLUP_EVENT => {
+                Ok(Some(LogRecord::Rollup(serde_json::from_value(value)?)))
+            }
+            Some(_) => Ok(None),
+        }
+    }
+
+    /// When the record was written; a rollup's window start.
+    pub(crate) fn ts(&self) -> DateTime<Utc> {
+        match self {
+            LogRecord::Turn(record) => record.ts,
+            LogRecord::Outcome(record) => record.ts,
+            LogRecord::Rollup(record) => record.ts,
+        }
+    }
+
+    pub(crate) fn model(&self) -> &str {
+        match self {
+            LogRecord::Turn(record) => &record.model,
+            LogRecord::Outcome(record) => &record.model,
+            LogRecord::Rollup(record) => &record.model,
+        }
+    }
+
+    /// Rollups belong to no session.
+    pub(crate) fn session_id(&self) -> Option<&str> {
+        match self {
+            LogRecord::Turn(record) => record.session_id.as_deref(),
+            LogRecord::Outcome(record) => record.session_id.as_deref(),
+            LogRecord::Rollup(_) => None,
+        }
+    }
+}
+
+/// Every turn record in the log at `path` and its rotated segments, oldest
+/// first. Lines of any schema version are read; versions newer than this
+/// build keep the fields it knows. Torn or malformed lines are skipped.
+pub fn read_turn_records(path: &Path) -> io::Result<Vec<TurnRecord>> {
+    read_turns(path, |_| true)
+}
+
+/// Turn records from the log and its rotated segments that `keep` accepts.
+pub(crate) fn read_turns(
+    path: &Path,
+    mut keep: impl FnMut(&TurnRecord) -> bool,
+) -> io::Result<Vec<TurnRecord>> {
+    let mut records = Vec::new();
+    for_each_log_line(path, |line| {
+        if let Ok(Some(record)) = TurnRecord::from_json_line(line) {
+            if keep(&record) {
+                records.push(record);
+            }
+        }
+    })?;
+    Ok(records)
+}
+
+/// Turns, request outcomes and rollups from the log and its rotated segments
+/// that `keep` accepts, in log order.
+pub(crate) fn read_log_records(
+    path: &Path,
+    mut keep: impl FnMut(&LogRecord) -> bool,
+) -> io::Result<Vec<LogRecord>> {
+    let mut records = Vec::new();
+    for_each_log_line(path, |line| {
+        // A torn final line from a crashed process should not hide the rest.
+        if let Ok(Some(record)) = LogRecord::from_json_line(line) {
+            if keep(&record) {
+                records.push(record);
+            }
+        }
+    })?;
+    Ok(records)
+}
+
+/// RFC 3339 in UTC with millisecond precision, which sorts as text.
+fn format_millis(ts: &DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+mod millis {
+    use super::*;
+
+    pub(super) fn serialize<S: Serializer>(
+        ts: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_millis(ts))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        DateTime::<Utc>::deserialize(deserializer)
+    }
+}
+
+mod optional_millis {
+    use super::*;
+
+    pub(super) fn serialize<S: Serializer>(
+        ts: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        ts.as_ref().map(format_millis).serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Option::<DateTime<Utc>>::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// A version 1 line as `to_json_line` writes it. Sinks receive the same
+    /// fields as a JSON object, whose key order is not significant.
+    const V1_LINE: &str = r#"{"schema_version":1,"ts":"2025-03-01T10:00:02.500Z","session_id":"sess-1","model":"gpt-5","reasoning_effort":"high","gateway_request_id":"req-1","cwd":"/work/widgets","git_remote":"https://github.com/acme/widgets.git","repo":"acme/widgets","branch":"main","tags":{"ticket":"ENG-1"},"prompt_tokens":200,"completion_tokens":40,"reasoning_tokens":12,"cached_tokens":64,"cache_creation_tokens":0,"total_tokens":240,"cost_usd":0.0125,"started_at":"2025-03-01T10:00:00.000Z","first_token_at":"2025-03-01T10:00:00.400Z","completed_at":"2025-03-01T10:00:02.500Z","ttft_ms":400,"duration_ms":2500,"output_tokens_per_sec":19.05}"#;
+
+    /// The line format of the first release, before versioning.
+    const V0_LINE: &str = r#"{"ts":"2025-03-01T09:00:00.000Z","session_id":"sess-0","model":"gpt-5","reasoning_effort":null,"prompt_tokens":100,"completion_tokens":10,"reasoning_tokens":0,"total_tokens":110}"#;
+
+    #[test]
+    fn version_1_lines_round_trip_byte_for_byte() {
+        let record = TurnRecord::from_json_line(V1_LINE)
+            .expect("parse")
+            .expect("turn");
+
+        assert_eq!(record.schema_version, TURN_RECORD_SCHEMA_VERSION);
+        assert_eq!(record.repo.as_deref(), Some("acme/widgets"));
+        assert_eq!(record.tags.get("ticket").map(String::as_str), Some("ENG-1"));
+        assert_eq!(record.to_json_line(), V1_LINE);
+        assert_eq!(
+            serde_json::to_value(&record).expect("to value"),
+            serde_json::from_str::<Value>(V1_LINE).expect("json")
+        );
+    }
+
+    #[test]
+    fn reads_unversioned_lines_and_skips_other_events() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        let outcome = r#"{"event":"request_outcome","ts":"2025-03-01T09:30:00.000Z","model":"gpt-5","succeeded":false}"#;
+        std::fs::write(
+            &path,
+            format!("{V0_LINE}\n{outcome}\n{{\"ts\":\n{V1_LINE}\n"),
+        )
+        .expect("write log");
+
+        let records = read_turn_records(&path).expect("read");
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0],
+            TurnRecord {
+                schema_version: 0,
+                ts: "2025-03-01T09:00:00Z".parse().expect("ts"),
+                session_id: Some("sess-0".to_string()),
+                model: "gpt-5".to_string(),
+                reasoning_effort: None,
+                gateway_request_id: None,
+                cwd: None,
+                git_remote: None,
+                repo: None,
+                branch: None,
+                tags: SessionTags::new(),
+                prompt_tokens: 100,
+                completion_tokens: 10,
+                reasoning_tokens: 0,
+                cached_tokens: 0,
+                cache_creation_tokens: 0,
+                total_tokens: 110,
+                cost_usd: None,
+                started_at: None,
+                first_token_at: None,
+                completed_at: None,
+                ttft_ms: None,
+                duration_ms: None,
+                output_tokens_per_sec: None,
+            }
+        );
+        assert_eq!(records[1].session_id.as_deref(), Some("sess-1"));
+    }
+}
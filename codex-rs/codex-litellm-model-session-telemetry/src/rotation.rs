@@ -0,0 +1,362 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::{DateTime, Duration, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::config::read_config_table;
+
+const GZIP_SUFFIX: &str = ".gz";
+
+fn default_keep() -> usize {
+    5
+}
+
+/// Rotation policy for the telemetry JSONL file, read from
+/// `[telemetry.rotation]` in `config.toml`:
+///
+/// ```toml
+/// [telemetry.rotation]
+/// max_bytes = 10_485_760
+/// max_age_days = 7
+/// keep = 5
+/// compress = true
+/// ```
+///
+/// The active file becomes `<name>.1` (or `<name>.1.gz`) when either limit
+/// is reached; older segments shift up and anything past `keep` is deleted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LogRotation {
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+    #[serde(default = "default_keep")]
+    pub keep: usize,
+    #[serde(default)]
+    pub compress: bool,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        Self {
+            max_bytes: None,
+            max_age_days: None,
+            keep: default_keep(),
+            compress: false,
+        }
+    }
+}
+
+impl LogRotation {
+    /// Reads `[telemetry.rotation]` from a `config.toml`. A missing file or
+    /// table means no rotation.
+    pub fn from_config_toml(path: &Path) -> io::Result<Option<Self>> {
+        read_config_table(path, &["telemetry", "rotation"])
+    }
+
+    pub(crate) fn should_rotate(
+        &self,
+        current_bytes: u64,
+        incoming_bytes: u64,
+        segment_started: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        if current_bytes == 0 {
+            return false;
+        }
+        let too_big = self
+            .max_bytes
+            .is_some_and(|max| current_bytes + incoming_bytes > max);
+        let too_old = self.max_age_days.is_some_and(|days| {
+            now - segment_started >= Duration::days(i64::try_from(days).unwrap_or(i64::MAX))
+        });
+        too_big || too_old
+    }
+}
+
+/// Moves the active file to `<name>.1`, shifting older segments up and
+/// pruning past `keep`, including segments left over from a larger `keep`.
+/// The caller reopens the active path afterwards.
+pub(crate) fn rotate(path: &Path, rotation: &LogRotation) -> io::Result<()> {
+    let keep = rotation.keep.max(1);
+    let mut rotated = rotated_segments(path);
+    rotated.sort_by_key(|(index, _)| std::cmp::Reverse(*index));
+    for (index, from) in rotated {
+        if index >= keep {
+            fs::remove_file(&from)?;
+            continue;
+        }
+        let suffix = if from.to_string_lossy().ends_with(GZIP_SUFFIX) {
+            GZIP_SUFFIX
+        } else {
+            ""
+        };
+        fs::rename(&from, segment_path(path, index + 1, suffix))?;
+    }
+
+    if rotation.compress {
+        let target = segment_path(path, 1, GZIP_SUFFIX);
+        let mut input = File::open(path)?;
+        let mut encoder = GzEncoder::new(create_private(&target)?, Compression::default());
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?.sync_all()?;
+        restrict_permissions(&target)?;
+        fs::remove_file(path)?;
+    } else {
+        let target = segment_path(path, 1, "");
+        fs::rename(path, &target)?;
+        restrict_permissions(&target)?;
+    }
+    Ok(())
+}
+
+/// Rotated segments that exist on disk, oldest first, followed by the active
+/// file itself.
+pub(crate) fn log_segments(path: &Path) -> Vec<PathBuf> {
+    let mut rotated = rotated_segments(path);
+    rotated.sort_by_key(|(index, _)| std::cmp::Reverse(*index));
+    let mut segments: Vec<PathBuf> = rotated.into_iter().map(|(_, path)| path).collect();
+    segments.push(path.to_path_buf());
+    segments
+}
+
+/// `<name>.<index>` and `<name>.<index>.gz` files next to `path`, unordered.
+fn rotated_segments(path: &Path) -> Vec<(usize, PathBuf)> {
+    let mut rotated: Vec<(usize, PathBuf)> = Vec::new();
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str()))
+    else {
+        return rotated;
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    let Ok(entries) = fs::read_dir(parent) else {
+        return rotated;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        let Some(rest) = file_name
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('.'))
+        else {
+            continue;
+        };
+        let index = rest.strip_suffix(GZIP_SUFFIX).unwrap_or(rest);
+        if let Ok(index) = index.parse::<usize>() {
+            rotated.push((index, entry.path()));
+        }
+    }
+    rotated
+}
+
+/// Opens a plain or gzip-compressed segment for line reading.
+pub(crate) fn open_segment(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    if path
+        .extension()
+        .is_some_and(|ext| ext == GZIP_SUFFIX.trim_start_matches('.'))
+    {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+fn segment_path(path: &Path, index: usize, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}{suffix}"));
+    PathBuf::from(name)
+}
+
+fn create_private(path: &Path) -> io::Result<File> {
+    let mut opts = OpenOptions::new();
+    opts.create(true).write(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    opts.open(path)
+}
+
+fn restrict_permissions(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::{Read, Write};
+
+    fn write(path: &Path, contents: &str) {
+        fs::write(path, contents).expect("write segment");
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .expect("read dir")
+            .map(|entry| {
+                entry
+                    .expect("entry")
+                    .file_name()
+                    .into_string()
+                    .expect("utf8")
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_when_the_next_write_would_pass_max_bytes_or_the_segment_is_old() {
+        let now = Utc::now();
+        let by_size = LogRotation {
+            max_bytes: Some(100),
+            ..LogRotation::default()
+        };
+        assert!(!by_size.should_rotate(60, 40, now, now));
+        assert!(by_size.should_rotate(60, 41, now, now));
+        // An empty file is never rotated, even by a single oversized batch.
+        assert!(!by_size.should_rotate(0, 1_000, now, now));
+
+        let by_age = LogRotation {
+            max_age_days: Some(7),
+            ..LogRotation::default()
+        };
+        assert!(!by_age.should_rotate(1, 0, now - Duration::days(6), now));
+        assert!(by_age.should_rotate(1, 0, now - Duration::days(7), now));
+        assert!(!LogRotation::default().should_rotate(
+            u64::MAX / 2,
+            1,
+            now - Duration::days(365),
+            now
+        ));
+    }
+
+    #[test]
+    fn compressed_segments_are_gzip_and_private() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        write(&path, "{\"turn\":1}\n");
+        let rotation = LogRotation {
+            compress: true,
+            ..LogRotation::default()
+        };
+
+        rotate(&path, &rotation).expect("rotate");
+
+        let segment = dir.path().join("telemetry.jsonl.1.gz");
+        assert_eq!(names(dir.path()), vec!["telemetry.jsonl.1.gz"]);
+        let mut raw = Vec::new();
+        File::open(&segment)
+            .expect("open segment")
+            .read_to_end(&mut raw)
+            .expect("read segment");
+        assert_eq!(&raw[..2], &[0x1f, 0x8b]);
+        let mut contents = String::new();
+        open_segment(&segment)
+            .expect("open segment")
+            .read_to_string(&mut contents)
+            .expect("decompress");
+        assert_eq!(contents, "{\"turn\":1}\n");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&segment)
+                .expect("metadata")
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn plain_segments_are_made_private() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        let mut file = File::create(&path).expect("create");
+        writeln!(file, "{{}}").expect("write");
+        drop(file);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).expect("chmod");
+        }
+
+        rotate(&path, &LogRotation::default()).expect("rotate");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let segment = dir.path().join("telemetry.jsonl.1");
+            let mode = fs::metadata(segment)
+                .expect("metadata")
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn keeps_the_newest_segments_even_after_keep_shrinks() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        let rotation = LogRotation {
+            keep: 3,
+            ..LogRotation::default()
+        };
+        for turn in 1..=5 {
+            write(&path, &format!("{turn}\n"));
+            rotate(&path, &rotation).expect("rotate");
+        }
+        assert_eq!(
+            names(dir.path()),
+            vec![
+                "telemetry.jsonl.1",
+                "telemetry.jsonl.2",
+                "telemetry.jsonl.3"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("telemetry.jsonl.3")).expect("oldest"),
+            "3\n"
+        );
+
+        // A smaller `keep` prunes every segment past it on the next rotation.
+        write(&path, "6\n");
+        let rotation = LogRotation {
+            keep: 1,
+            compress: true,
+            ..LogRotation::default()
+        };
+        rotate(&path, &rotation).expect("rotate");
+        assert_eq!(names(dir.path()), vec!["telemetry.jsonl.1.gz"]);
+        assert_eq!(
+            log_segments(&path),
+            vec![dir.path().join("telemetry.jsonl.1.gz"), path.clone()]
+        );
+    }
+}
@@ -0,0 +1,363 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::read_config_table;
+use crate::{rotation, LOG_ROTATION};
+
+/// Destination for telemetry records. Every registered sink receives each
+/// record: one JSON object per turn or request outcome, in the same shape
+/// as a line of the JSONL log. Sinks run on the background writer thread.
+pub trait TelemetrySink: Send {
+    fn record(&mut self, record: &Value) -> io::Result<()>;
+
+    /// Called after each batch of records and when the sink is replaced.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends records to a JSONL file, rotating it per `[telemetry.rotation]`.
+///
+/// Several processes can share one log (`codex` and concurrent
+/// `codex-litellm exec` runs under the same `CODEX_HOME`). Records are
+/// buffered until `flush`, which takes an exclusive advisory lock on
+/// `<name>.lock`, rotates if needed and appends the whole batch in one write,
+/// so lines from different processes never interleave.
+pub struct JsonlSink {
+    path: PathBuf,
+    lock_path: PathBuf,
+    pending: Vec<u8>,
+}
+
+impl JsonlSink {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        open_private(&path)?;
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        open_private(&lock_path)?;
+        Ok(Self {
+            path,
+            lock_path,
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the pending batch while holding the lock. The log is opened
+    /// afresh each time because another process may have rotated it.
+    fn write_pending(&mut self) -> io::Result<()> {
+        let lock = open_private(&self.lock_path)?;
+        lock.lock()?;
+        let mut file = open_private(&self.path)?;
+        if rotate_if_needed(&self.path, &file, self.pending.len() as u64)? {
+            file = open_private(&self.path)?;
+        }
+        // A failed batch is dropped rather than retried, which could
+        // duplicate a partial write.
+        let written = file.write_all(&self.pending);
+        self.pending.clear();
+        written
+        // Dropping `lock` releases it.
+    }
+}
+
+fn open_private(path: &Path) -> io::Result<File> {
+    let mut opts = OpenOptions::new();
+    opts.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    opts.open(path)
+}
+
+/// Rotates `path` when appending `incoming_bytes` to `file` would break the
+/// configured limits. Returns whether it did.
+fn rotate_if_needed(path: &Path, file: &File, incoming_bytes: u64) -> io::Result<bool> {
+    let Some(rotation) = LOG_ROTATION.lock().clone() else {
+        return Ok(false);
+    };
+    let metadata = file.metadata()?;
+    let segment_started = metadata
+        .created()
+        .or_else(|_| metadata.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now());
+    if !rotation.should_rotate(metadata.len(), incoming_bytes, segment_started, Utc::now()) {
+        return Ok(false);
+    }
+    rotation::rotate(path, &rotation)?;
+    Ok(true)
+}
+
+impl TelemetrySink for JsonlSink {
+    fn record(&mut self, record: &Value) -> io::Result<()> {
+        serde_json::to_writer(&mut self.pending, record)?;
+        self.pending.push(b'\n');
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        self.write_pending()
+    }
+}
+
+impl Drop for JsonlSink {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            eprintln!(
+                "[codex-litellm-model-session-telemetry] failed to write telemetry log: {err}"
+            );
+        }
+    }
+}
+
+/// Keeps the most recent records in memory. Clones share the same buffer, so
+/// register one clone and read from another.
+#[derive(Debug, Clone)]
+pub struct MemorySink {
+    records: Arc<Mutex<VecDeque<Value>>>,
+    capacity: usize,
+}
+
+impl MemorySink {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Buffered records, oldest first.
+    pub fn records(&self) -> Vec<Value> {
+        self.records.lock().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().clear();
+    }
+}
+
+impl TelemetrySink for MemorySink {
+    fn record(&mut self, record: &Value) -> io::Result<()> {
+        let mut records = self.records.lock();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record.clone());
+        Ok(())
+    }
+}
+
+fn default_webhook_timeout_ms() -> u64 {
+    2_000
+}
+
+/// `[telemetry.webhook]` in `config.toml`:
+///
+/// ```toml
+/// [telemetry.webhook]
+/// url = "https://hooks.example.com/codex-litellm"
+/// headers = { Authorization = "Bearer ..." }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_webhook_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl WebhookConfig {
+    pub fn from_config_toml(path: &Path) -> io::Result<Option<Self>> {
+        read_config_table(path, &["telemetry", "webhook"])
+    }
+}
+
+/// POSTs each record as a JSON body to a webhook.
+pub struct WebhookSink {
+    agent: ureq::Agent,
+    config: WebhookConfig,
+}
+
+impl WebhookSink {
+    pub fn new(config: WebhookConfig) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build();
+        Self { agent, config }
+    }
+}
+
+impl TelemetrySink for WebhookSink {
+    fn record(&mut self, record: &Value) -> io::Result<()> {
+        let mut request = self
+            .agent
+            .post(&self.config.url)
+            .set("Content-Type", "application/json");
+        for (name, value) in &self.config.headers {
+            request = request.set(name, value);
+        }
+        request
+            .send_string(&record.to_string())
+            .map(drop)
+            .map_err(|err| io::Error::other(format!("POST {} failed: {err}", self.config.url)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        configure_log_rotation, for_each_log_line, record_turn, register_sink, unregister_sink,
+        LogRotation, TurnUsage,
+    };
+    use std::process::{Command, Stdio};
+
+    /// Set in child processes spawned by
+    /// `concurrent_processes_append_whole_records`: `<log path>|<writer id>`.
+    const CHILD_WRITER_ENV: &str = "CODEX_LITELLM_TEST_JSONL_WRITER";
+    const CHILD_WRITERS: usize = 4;
+    const RECORDS_PER_CHILD: usize = 200;
+    /// Large enough that a batch spans many pages and the log rotates often.
+    const PADDING: usize = 3000;
+
+    #[test]
+    fn memory_sink_keeps_the_most_recent_records() {
+        let sink = MemorySink::new(2);
+        let mut writer = sink.clone();
+        for n in 1..=3 {
+            writer.record(&Value::from(n)).expect("record");
+        }
+
+        assert_eq!(sink.records(), vec![Value::from(2), Value::from(3)]);
+    }
+
+    #[test]
+    fn record_turn_fans_out_to_every_registered_sink() {
+        let first = MemorySink::new(256);
+        let second = MemorySink::new(256);
+        register_sink("fan-out-first", Box::new(first.clone()));
+        register_sink("fan-out-second", Box::new(second.clone()));
+
+        record_turn(
+            Some("fan-out-session"),
+            "m",
+            None,
+            None,
+            TurnUsage {
+                total_tokens: 42,
+                ..Default::default()
+            },
+            None,
+        );
+        assert!(unregister_sink("fan-out-first"));
+        assert!(unregister_sink("fan-out-second"));
+
+        for sink in [first, second] {
+            let records: Vec<Value> = sink
+                .records()
+                .into_iter()
+                .filter(|record| record["session_id"] == "fan-out-session")
+                .collect();
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0]["total_tokens"], 42);
+        }
+    }
+
+    /// Runs only as a child of `concurrent_processes_append_whole_records`.
+    #[test]
+    fn concurrent_writer_child() {
+        let Ok(spec) = std::env::var(CHILD_WRITER_ENV) else {
+            return;
+        };
+        let (path, writer) = spec.split_once('|').expect("child spec");
+        configure_log_rotation(Some(LogRotation {
+            max_bytes: Some(64 * 1024),
+            keep: 1000,
+            ..Default::default()
+        }));
+        let mut sink = JsonlSink::open(PathBuf::from(path)).expect("open log");
+        for seq in 0..RECORDS_PER_CHILD {
+            let record = serde_json::json!({
+                "writer": writer,
+                "seq": seq,
+                "padding": "x".repeat(PADDING),
+            });
+            sink.record(&record).expect("record");
+            if seq % 10 == 9 {
+                sink.flush().expect("flush");
+            }
+        }
+        sink.flush().expect("flush");
+    }
+
+    #[test]
+    fn concurrent_processes_append_whole_records() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.jsonl");
+        let exe = std::env::current_exe().expect("test binary");
+
+        let children: Vec<_> = (0..CHILD_WRITERS)
+            .map(|writer| {
+                Command::new(&exe)
+                    .args([
+                        "--exact",
+                        "sink::tests::concurrent_writer_child",
+                        "--test-threads=1",
+                    ])
+                    .env(CHILD_WRITER_ENV, format!("{}|{writer}", path.display()))
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .expect("spawn writer")
+            })
+            .collect();
+        for mut child in children {
+            assert!(child.wait().expect("wait for writer").success());
+        }
+
+        let mut seqs: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+        let mut corrupt = 0;
+        for_each_log_line(&path, |line| match serde_json::from_str::<Value>(line) {
+            Ok(record) if record["padding"].as_str().map(str::len) == Some(PADDING) => {
+                seqs.entry(record["writer"].as_str().unwrap_or_default().to_string())
+                    .or_default()
+                    .push(record["seq"].as_u64().unwrap_or(u64::MAX));
+            }
+            _ => corrupt += 1,
+        })
+        .expect("read log");
+
+        assert_eq!(corrupt, 0);
+        assert_eq!(seqs.len(), CHILD_WRITERS);
+        for (writer, seqs) in seqs {
+            assert_eq!(
+                seqs,
+                (0..RECORDS_PER_CHILD as u64).collect::<Vec<_>>(),
+                "writer {writer}"
+            );
+        }
+    }
+}
@@ -0,0 +1,523 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Deserialize;
+use serde_json::Value;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::read_config_table;
+use crate::record::{LogRecord, OutcomeRecord};
+use crate::sink::TelemetrySink;
+use crate::{
+    normalize_session_id, replay_record, session_snapshot, RequestOutcome, SessionTags,
+    SessionTelemetryEntry, SessionTelemetrySnapshot, TurnRecord, WorkspaceInfo,
+    TURN_RECORD_SCHEMA_VERSION,
+};
+
+/// Schema changes in order. `PRAGMA user_version` records how many have been
+/// applied; append new steps, never edit shipped ones.
+const MIGRATIONS: &[&str] = &[
+    // 1: turns, request outcomes and the sessions/models they belong to.
+    "CREATE TABLE sessions (
+        session_id TEXT PRIMARY KEY,
+        first_seen TEXT NOT NULL,
+        last_seen TEXT NOT NULL
+    );
+    CREATE TABLE models (
+        model TEXT PRIMARY KEY,
+        first_seen TEXT NOT NULL,
+        last_seen TEXT NOT NULL
+    );
+    CREATE TABLE turns (
+        id INTEGER PRIMARY KEY,
+        ts TEXT NOT NULL,
+        session_id TEXT NOT NULL REFERENCES sessions(session_id),
+        model TEXT NOT NULL REFERENCES models(model),
+        reasoning_effort TEXT,
+        gateway_request_id TEXT,
+        prompt_tokens INTEGER NOT NULL,
+        completion_tokens INTEGER NOT NULL,
+        reasoning_tokens INTEGER NOT NULL,
+        cached_tokens INTEGER NOT NULL,
+        cache_creation_tokens INTEGER NOT NULL,
+        total_tokens INTEGER NOT NULL,
+        cost_usd REAL,
+        ttft_ms INTEGER,
+        duration_ms INTEGER,
+        output_tokens_per_sec REAL
+    );
+    CREATE INDEX turns_session_ts ON turns(session_id, ts);
+    CREATE INDEX turns_model_ts ON turns(model, ts);
+    CREATE INDEX turns_ts ON turns(ts);
+    CREATE TABLE request_outcomes (
+        id INTEGER PRIMARY KEY,
+        ts TEXT NOT NULL,
+        session_id TEXT NOT NULL REFERENCES sessions(session_id),
+        model TEXT NOT NULL REFERENCES models(model),
+        gateway_request_id TEXT,
+        error_class TEXT,
+        http_status INTEGER,
+        retried_without_reasoning INTEGER NOT NULL,
+        succeeded INTEGER NOT NULL
+    );
+    CREATE INDEX request_outcomes_session_ts ON request_outcomes(session_id, ts);
+    CREATE INDEX request_outcomes_model_ts ON request_outcomes(model, ts);",
+    // 2: the workspace each session ran in, for billing by repository.
+    "ALTER TABLE sessions ADD COLUMN cwd TEXT;
+    ALTER TABLE sessions ADD COLUMN git_remote TEXT;
+    ALTER TABLE sessions ADD COLUMN repo TEXT;
+    ALTER TABLE sessions ADD COLUMN branch TEXT;
+    CREATE INDEX sessions_repo ON sessions(repo);",
+    // 3: session tags as a JSON object.
+    "ALTER TABLE sessions ADD COLUMN tags TEXT;",
+];
+
+/// `[telemetry.sqlite]` in `config.toml`:
+///
+/// ```toml
+/// [telemetry.sqlite]
+/// path = "/home/me/.codex/logs/codex-litellm-telemetry.sqlite"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SqliteConfig {
+    pub path: PathBuf,
+}
+
+impl SqliteConfig {
+    pub fn from_config_toml(path: &Path) -> io::Result<Option<Self>> {
+        read_config_table(path, &["telemetry", "sqlite"])
+    }
+}
+
+/// Telemetry history in SQLite. Registered as a sink it stores every record;
+/// opened separately it answers snapshot queries for any session on record.
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let conn = Connection::open(path).map_err(sql_error)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")
+            .map_err(sql_error)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(sql_error)?;
+        let mut store = Self { conn };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    pub fn open_in_memory() -> io::Result<Self> {
+        let conn = Connection::open_in_memory().map_err(sql_error)?;
+        let mut store = Self { conn };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    /// Number of migrations applied to this database.
+    pub fn schema_version(&self) -> io::Result<usize> {
+        let version: i64 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(sql_error)?;
+        Ok(usize::try_from(version).unwrap_or_default())
+    }
+
+    fn migrate(&mut self) -> io::Result<()> {
+        let applied = self.schema_version()?;
+        if applied > MIGRATIONS.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "telemetry database schema version {applied} is newer than this build supports ({})",
+                    MIGRATIONS.len()
+                ),
+            ));
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+            let tx = self.conn.transaction().map_err(sql_error)?;
+            tx.execute_batch(migration).map_err(sql_error)?;
+            tx.pragma_update(None, "user_version", index + 1)
+                .map_err(sql_error)?;
+            tx.commit().map_err(sql_error)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds a session's snapshot from stored history, with the same
+    /// totals, percentiles and failure counters as the live in-memory view.
+    pub fn snapshot(
+        &self,
+        session_id: Option<&str>,
+    ) -> io::Result<Option<SessionTelemetrySnapshot>> {
+        let key = normalize_session_id(session_id);
+        let Some((workspace, tags)) = self
+            .conn
+            .query_row(
+                "SELECT cwd, git_remote, repo, branch, tags FROM sessions WHERE session_id = ?1",
+                [&key],
+                |row| Ok((workspace_from_row(row)?, row.get::<_, Option<String>>(4)?)),
+            )
+            .optional()
+            .map_err(sql_error)?
+        else {
+            return Ok(None);
+        };
+
+        let mut entry = SessionTelemetryEntry::default();
+        let mut records: Vec<LogRecord> = Vec::new();
+        let mut turns = self
+            .conn
+            .prepare(
+                "SELECT ts, model, reasoning_effort, gateway_request_id, prompt_tokens,
+                        completion_tokens, reasoning_tokens, cached_tokens,
+                        cache_creation_tokens, total_tokens, cost_usd, ttft_ms, duration_ms,
+                        output_tokens_per_sec
+                 FROM turns WHERE session_id = ?1 ORDER BY ts, id",
+            )
+            .map_err(sql_error)?;
+        let rows = turns
+            .query_map([&key], |row| turn_from_row(&key, row))
+            .map_err(sql_error)?;
+        for row in rows {
+            records.push(row.map_err(sql_error)?);
+        }
+        let mut outcomes = self
+            .conn
+            .prepare(
+                "SELECT ts, model, gateway_request_id, error_class, http_status,
+                        retried_without_reasoning, succeeded
+                 FROM request_outcomes WHERE session_id = ?1 ORDER BY ts, id",
+            )
+            .map_err(sql_error)?;
+        let rows = outcomes
+            .query_map([&key], |row| outcome_from_row(&key, row))
+            .map_err(sql_error)?;
+        for row in rows {
+            records.push(row.map_err(sql_error)?);
+        }
+        // Stable, so a turn and an outcome with the same timestamp keep
+        // their insertion order within each table.
+        records.sort_by_key(LogRecord::ts);
+
+        for record in &records {
+            replay_record(&mut entry, record);
+        }
+        entry.workspace = workspace;
+        entry.tags = tags
+            .and_then(|tags| serde_json::from_str(&tags).ok())
+            .unwrap_or_default();
+        Ok(Some(session_snapshot(&entry)))
+    }
+
+    /// Upserts the session and model rows. A record carrying a workspace or
+    /// tags replaces the session's stored ones; outcomes and older turns keep
+    /// them.
+    fn touch(
+        conn: &Connection,
+        session_id: &str,
+        model: &str,
+        turn: Option<&TurnRecord>,
+        ts: &str,
+    ) -> io::Result<()> {
+        let workspace = turn.and_then(TurnRecord::workspace);
+        let workspace = workspace.as_ref();
+        let tags = turn
+            .filter(|turn| !turn.tags.is_empty())
+            .map(|turn| serde_json::to_string(&turn.tags))
+            .transpose()?;
+        conn.execute(
+            "INSERT INTO sessions (session_id, first_seen, last_seen, cwd, git_remote, repo,
+                    branch, tags)
+                 VALUES (?1, ?2, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(session_id) DO UPDATE SET
+                    last_seen = excluded.last_seen,
+                    cwd = IIF(excluded.cwd IS NULL, cwd, excluded.cwd),
+                    git_remote = IIF(excluded.cwd IS NULL, git_remote, excluded.git_remote),
+                    repo = IIF(excluded.cwd IS NULL, repo, excluded.repo),
+                    branch = IIF(excluded.cwd IS NULL, branch, excluded.branch),
+                    tags = COALESCE(excluded.tags, tags)",
+            params![
+                session_id,
+                ts,
+                workspace.map(|workspace| workspace.cwd.to_string_lossy().into_owned()),
+                workspace.and_then(|workspace| workspace.git_remote.as_deref()),
+                workspace.and_then(|workspace| workspace.repo.as_deref()),
+                workspace.and_then(|workspace| workspace.branch.as_deref()),
+                tags,
+            ],
+        )
+        .map_err(sql_error)?;
+        conn.execute(
+            "INSERT INTO models (model, first_seen, last_seen) VALUES (?1, ?2, ?2)
+                 ON CONFLICT(model) DO UPDATE SET last_seen = excluded.last_seen",
+            params![model, ts],
+        )
+        .map_err(sql_error)?;
+        Ok(())
+    }
+}
+
+impl TelemetrySink for SqliteStore {
+    fn record(&mut self, record: &Value) -> io::Result<()> {
+        let Some(record) = LogRecord::from_value(record.clone())? else {
+            return Ok(());
+        };
+        let session_id = normalize_session_id(record.session_id());
+        // Stored in the same fixed-width form as the log, so text order is
+        // time order.
+        let ts = record.ts().to_rfc3339_opts(SecondsFormat::Millis, true);
+
+        // The session and model upserts commit together with the row that
+        // references them, or not at all.
+        let tx = self.conn.transaction().map_err(sql_error)?;
+        match &record {
+            // Aggregate rollups carry no session to attach them to.
+            LogRecord::Rollup(_) => {}
+            LogRecord::Outcome(record) => {
+                Self::touch(&tx, &session_id, &record.model, None, &ts)?;
+                let outcome = &record.outcome;
+                tx.execute(
+                    "INSERT INTO request_outcomes (ts, session_id, model, gateway_request_id,
+                            error_class, http_status, retried_without_reasoning, succeeded)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        ts,
+                        session_id,
+                        record.model,
+                        outcome.gateway_request_id,
+                        outcome.error_class.map(|class| class.as_str()),
+                        outcome.http_status,
+                        outcome.retried_without_reasoning,
+                        outcome.succeeded,
+                    ],
+                )
+                .map_err(sql_error)?;
+            }
+            LogRecord::Turn(record) => {
+                Self::touch(&tx, &session_id, &record.model, Some(record), &ts)?;
+                tx.execute(
+                    "INSERT INTO turns (ts, session_id, model, reasoning_effort,
+                            gateway_request_id, prompt_tokens, completion_tokens,
+                            reasoning_tokens, cached_tokens, cache_creation_tokens,
+                            total_tokens, cost_usd, ttft_ms, duration_ms, output_tokens_per_sec)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14,
+                            ?15)",
+                    params![
+                        ts,
+                        session_id,
+                        record.model,
+                        record.reasoning_effort,
+                        record.gateway_request_id,
+                        record.prompt_tokens,
+                        record.completion_tokens,
+                        record.reasoning_tokens,
+                        record.cached_tokens,
+                        record.cache_creation_tokens,
+                        record.total_tokens,
+                        record.cost_usd,
+                        record.ttft_ms,
+                        record.duration_ms,
+                        record.output_tokens_per_sec,
+                    ],
+                )
+                .map_err(sql_error)?;
+            }
+        }
+        tx.commit().map_err(sql_error)
+    }
+}
+
+/// Rows are written by `record`, so a timestamp that fails to parse means a
+/// hand-edited database; such rows sort first.
+fn parse_ts(value: String) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(&value)
+        .map(|ts| ts.with_timezone(&Utc))
+        .unwrap_or_default()
+}
+
+/// `None` for a session that never had a workspace set.
+fn workspace_from_row(row: &Row<'_>) -> rusqlite::Result<Option<WorkspaceInfo>> {
+    let cwd: Option<String> = row.get(0)?;
+    Ok(match cwd {
+        Some(cwd) => Some(WorkspaceInfo {
+            cwd: PathBuf::from(cwd),
+            git_remote: row.get(1)?,
+            repo: row.get(2)?,
+            branch: row.get(3)?,
+        }),
+        None => None,
+    })
+}
+
+fn turn_from_row(session_id: &str, row: &Row<'_>) -> rusqlite::Result<LogRecord> {
+    Ok(LogRecord::Turn(Box::new(TurnRecord {
+        schema_version: TURN_RECORD_SCHEMA_VERSION,
+        ts: parse_ts(row.get(0)?),
+        session_id: Some(session_id.to_string()),
+        model: row.get(1)?,
+        reasoning_effort: row.get(2)?,
+        gateway_request_id: row.get(3)?,
+        // Kept once per session on the `sessions` row.
+        cwd: None,
+        git_remote: None,
+        repo: None,
+        branch: None,
+        tags: SessionTags::new(),
+        prompt_tokens: row.get(4)?,
+        completion_tokens: row.get(5)?,
+        reasoning_tokens: row.get(6)?,
+        cached_tokens: row.get(7)?,
+        cache_creation_tokens: row.get(8)?,
+        total_tokens: row.get(9)?,
+        cost_usd: row.get(10)?,
+        started_at: None,
+        first_token_at: None,
+        completed_at: None,
+        ttft_ms: row.get(11)?,
+        duration_ms: row.get(12)?,
+        output_tokens_per_sec: row.get(13)?,
+    })))
+}
+
+fn outcome_from_row(session_id: &str, row: &Row<'_>) -> rusqlite::Result<LogRecord> {
+    let error_class: Option<String> = row.get(3)?;
+    Ok(LogRecord::Outcome(OutcomeRecord {
+        ts: parse_ts(row.get(0)?),
+        session_id: Some(session_id.to_string()),
+        model: row.get(1)?,
+        outcome: RequestOutcome {
+            error_class: error_class
+                .and_then(|class| serde_json::from_value(Value::String(class)).ok()),
+            http_status: row.get(4)?,
+            retried_without_reasoning: row.get(5)?,
+            succeeded: row.get(6)?,
+            gateway_request_id: row.get(2)?,
+        },
+    }))
+}
+
+fn sql_error(err: rusqlite::Error) -> io::Error {
+    io::Error::other(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        outcome_record, turn_record, ErrorClass, RequestOutcome, SessionTags, TurnEvent, TurnUsage,
+    };
+    use pretty_assertions::assert_eq;
+
+    fn turn(total_tokens: i64, effort: &str, workspace: Option<&WorkspaceInfo>) -> Value {
+        let tags = SessionTags::from([("ticket".to_string(), "ENG-123".to_string())]);
+        turn_record(&TurnEvent {
+            session_id: Some("sqlite-session"),
+            model: "vercel/minimax-m2.7",
+            reasoning_effort: Some(effort),
+            gateway_request_id: None,
+            workspace,
+            tags: &tags,
+            usage: TurnUsage {
+                prompt_tokens: total_tokens - 10,
+                completion_tokens: 10,
+                total_tokens,
+                ..Default::default()
+            },
+            timing: None,
+            metrics: Default::default(),
+        })
+    }
+
+    #[test]
+    fn migrates_a_fresh_database_once() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("telemetry.sqlite");
+
+        let store = SqliteStore::open(&path).expect("open");
+        assert_eq!(store.schema_version().expect("version"), MIGRATIONS.len());
+        drop(store);
+
+        let reopened = SqliteStore::open(&path).expect("reopen");
+        assert_eq!(
+            reopened.schema_version().expect("version"),
+            MIGRATIONS.len()
+        );
+    }
+
+    #[test]
+    fn snapshot_matches_recorded_turns_and_outcomes() {
+        let workspace = WorkspaceInfo {
+            cwd: PathBuf::from("/src/widgets"),
+            git_remote: Some("git@github.com:acme/widgets.git".to_string()),
+            repo: Some("acme/widgets".to_string()),
+            branch: Some("main".to_string()),
+        };
+        let mut store = SqliteStore::open_in_memory().expect("open");
+        store
+            .record(&turn(110, "high", Some(&workspace)))
+            .expect("first turn");
+        store.record(&turn(60, "low", None)).expect("second turn");
+        store
+            .record(&outcome_record(
+                Some("sqlite-session"),
+                "vercel/minimax-m2.7",
+                &RequestOutcome {
+                    error_class: Some(ErrorClass::RateLimited),
+                    http_status: Some(429),
+                    succeeded: false,
+                    ..Default::default()
+                },
+            ))
+            .expect("outcome");
+
+        let snapshot = store
+            .snapshot(Some("sqlite-session"))
+            .expect("query")
+            .expect("session on record");
+
+        assert_eq!(snapshot.total_turns, 2);
+        assert_eq!(snapshot.total_tokens, 170);
+        assert_eq!(snapshot.last_reasoning_effort.as_deref(), Some("low"));
+        assert_eq!(snapshot.workspace, Some(workspace));
+        assert_eq!(snapshot.tags["ticket"], "ENG-123");
+        let model = &snapshot.models[0];
+        assert_eq!(model.completion_tokens, 20);
+        assert_eq!(model.failed_requests, 1);
+        assert_eq!(
+            model.errors_by_class.get(&ErrorClass::RateLimited),
+            Some(&1)
+        );
+        assert!(store.snapshot(Some("unknown")).expect("query").is_none());
+    }
+
+    #[test]
+    fn a_failed_insert_leaves_no_session_or_model_behind() {
+        let mut store = SqliteStore::open_in_memory().expect("open");
+        store
+            .conn
+            .execute_batch(
+                "CREATE TRIGGER refuse_turns BEFORE INSERT ON turns
+                 BEGIN SELECT RAISE(ABORT, 'refused'); END;",
+            )
+            .expect("trigger");
+
+        assert!(store.record(&turn(110, "high", None)).is_err());
+
+        let rows: (i64, i64) = store
+            .conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM sessions), (SELECT COUNT(*) FROM models)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("count");
+        assert_eq!(rows, (0, 0));
+        assert!(store.conn.is_autocommit());
+    }
+}
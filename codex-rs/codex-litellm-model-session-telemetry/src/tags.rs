@@ -0,0 +1,51 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use std::collections::BTreeMap;
+
+/// Labels attached to a session with `codex-litellm --tag key=value`.
+pub type SessionTags = BTreeMap<String, String>;
+
+/// Parses one `key=value` tag. Tags travel in HTTP headers and LiteLLM joins
+/// them with commas, so both halves must be printable ASCII without commas.
+pub fn parse_session_tag(raw: &str) -> Result<(String, String), String> {
+    let Some((key, value)) = raw.split_once('=') else {
+        return Err(format!("invalid tag `{raw}`; expected key=value"));
+    };
+    let (key, value) = (key.trim(), value.trim());
+    if key.is_empty() || value.is_empty() {
+        return Err(format!(
+            "invalid tag `{raw}`; key and value must not be empty"
+        ));
+    }
+    let allowed = |c: char| c.is_ascii_graphic() && c != ',';
+    if !key.chars().all(allowed) || !value.chars().all(|c| c == ' ' || allowed(c)) {
+        return Err(format!(
+            "invalid tag `{raw}`; use printable ASCII without commas"
+        ));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_key_value_tags_and_rejects_header_unsafe_ones() {
+        assert_eq!(
+            parse_session_tag("ticket=ENG-123"),
+            Ok(("ticket".to_string(), "ENG-123".to_string()))
+        );
+        assert_eq!(
+            parse_session_tag(" team = infra platform "),
+            Ok(("team".to_string(), "infra platform".to_string()))
+        );
+        for raw in ["ticket", "=x", "team=", "team=a,b", "tëam=x"] {
+            assert!(parse_session_tag(raw).is_err(), "{raw}");
+        }
+    }
+}
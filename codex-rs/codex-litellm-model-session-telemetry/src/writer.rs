@@ -0,0 +1,317 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use parking_lot::{Condvar, Mutex, MutexGuard};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::thread::JoinHandle;
+
+use crate::{privacy, refresh_metrics_textfile, SINKS};
+
+/// Records waiting for the writer thread. When full, the oldest record is
+/// dropped so a stalled sink never blocks a turn.
+const QUEUE_CAPACITY: usize = 4096;
+
+/// Records written between sink flushes.
+const BATCH_SIZE: usize = 256;
+
+static WRITER: Lazy<Writer> = Lazy::new(Writer::default);
+
+#[derive(Default)]
+struct WriterState {
+    queue: VecDeque<Value>,
+    /// The writer thread holds a batch taken off `queue`.
+    writing: bool,
+    stopping: bool,
+    dropped: u64,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct Writer {
+    state: Mutex<WriterState>,
+    /// Signalled when records are queued or shutdown is requested.
+    work: Condvar,
+    /// Signalled when the queue is empty and no batch is being written.
+    drained: Condvar,
+}
+
+/// Queues records for every registered sink. Serialization and I/O happen
+/// on the writer thread, which is started on first use, even with nothing
+/// to queue, so it can close aggregate rollup windows while idle.
+pub(crate) fn enqueue(records: Vec<Value>) {
+    let mut state = WRITER.state.lock();
+    for record in records {
+        if push_bounded(&mut state.queue, record, QUEUE_CAPACITY) {
+            state.dropped += 1;
+        }
+    }
+    // While shutting down, the stopping writer or `shutdown` itself drains
+    // the queue; a second writer could reorder records.
+    if state.thread.is_none() && !state.stopping {
+        match std::thread::Builder::new()
+            .name("codex-litellm-telemetry-writer".to_string())
+            .spawn(run)
+        {
+            Ok(handle) => state.thread = Some(handle),
+            Err(err) => {
+                eprintln!(
+                    "[codex-litellm-model-session-telemetry] failed to start telemetry writer, writing inline: {err}"
+                );
+                let batch: Vec<Value> = state.queue.drain(..).collect();
+                drop(state);
+                write_batch(&batch);
+                return;
+            }
+        }
+    }
+    WRITER.work.notify_one();
+}
+
+/// Appends `record`, dropping the oldest entry if the queue is at
+/// `capacity`. Returns whether one was dropped.
+fn push_bounded(queue: &mut VecDeque<Value>, record: Value, capacity: usize) -> bool {
+    let dropped = queue.len() >= capacity && queue.pop_front().is_some();
+    queue.push_back(record);
+    dropped
+}
+
+/// Blocks until every queued record has been handed to the sinks and the
+/// sinks have been flushed.
+pub fn flush() {
+    let mut state = WRITER.state.lock();
+    while !state.queue.is_empty() || state.writing {
+        WRITER.drained.wait(&mut state);
+    }
+}
+
+/// Writes the open aggregate rollup, drains pending records, flushes every
+/// sink and stops the writer thread. Call before the process exits; records
+/// queued later start a new writer.
+pub fn shutdown() {
+    let rollups = privacy::take_rollups();
+    if !rollups.is_empty() {
+        enqueue(rollups);
+    }
+    let handle = {
+        let mut state = WRITER.state.lock();
+        state.stopping = true;
+        WRITER.work.notify_one();
+        state.thread.take()
+    };
+    if let Some(handle) = handle {
+        if handle.join().is_err() {
+            eprintln!("[codex-litellm-model-session-telemetry] telemetry writer panicked");
+        }
+    }
+    // Records queued after the writer exited but before `stopping` clears.
+    let mut state = WRITER.state.lock();
+    let pending: Vec<Value> = state.queue.drain(..).collect();
+    if !pending.is_empty() {
+        write_batch(&pending);
+    }
+    state.stopping = false;
+    WRITER.drained.notify_all();
+}
+
+fn run() {
+    loop {
+        let (batch, dropped) = {
+            let mut state = WRITER.state.lock();
+            wait_for_work(&mut state);
+            if state.queue.is_empty() {
+                return;
+            }
+            let take = state.queue.len().min(BATCH_SIZE);
+            state.writing = true;
+            (
+                state.queue.drain(..take).collect::<Vec<_>>(),
+                std::mem::take(&mut state.dropped),
+            )
+        };
+        if dropped > 0 {
+            eprintln!(
+                "[codex-litellm-model-session-telemetry] telemetry queue full, dropped {dropped} oldest records"
+            );
+        }
+        write_batch(&batch);
+
+        let mut state = WRITER.state.lock();
+        state.writing = false;
+        if state.queue.is_empty() {
+            WRITER.drained.notify_all();
+        }
+    }
+}
+
+/// Waits until records are queued or shutdown is requested. While an
+/// aggregate rollup window is open, wakes when it ends and queues its lines,
+/// so the last window before a quiet spell is not held back.
+fn wait_for_work(state: &mut MutexGuard<'_, WriterState>) {
+    while state.queue.is_empty() && !state.stopping {
+        // Never hold the writer lock while taking the privacy lock.
+        let due = MutexGuard::unlocked(state, privacy::rollup_due_at);
+        if !state.queue.is_empty() || state.stopping {
+            return;
+        }
+        let Some(due) = due else {
+            WRITER.work.wait(state);
+            continue;
+        };
+        let wait = (due - Utc::now()).to_std().unwrap_or_default();
+        if WRITER.work.wait_for(state, wait).timed_out() {
+            let rollups = MutexGuard::unlocked(state, || privacy::take_elapsed_rollups(Utc::now()));
+            state.queue.extend(rollups);
+        }
+    }
+}
+
+/// Hands each record to every registered sink, then flushes them once and
+/// refreshes the metrics textfile. A failing sink is reported and does not
+/// stop the others.
+fn write_batch(batch: &[Value]) {
+    let mut sinks = SINKS.lock();
+    for record in batch {
+        for (name, sink) in sinks.iter_mut() {
+            if let Err(err) = sink.record(record) {
+                eprintln!(
+                    "[codex-litellm-model-session-telemetry] {name} sink failed to write record: {err}"
+                );
+            }
+        }
+    }
+    for (name, sink) in sinks.iter_mut() {
+        if let Err(err) = sink.flush() {
+            eprintln!("[codex-litellm-model-session-telemetry] {name} sink failed to flush: {err}");
+        }
+    }
+    drop(sinks);
+    refresh_metrics_textfile();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        configure_privacy, record_turn, register_sink, unregister_sink, MemorySink, PrivacyConfig,
+        PrivacyMode, TelemetrySink, TurnUsage,
+    };
+    use std::io;
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    /// Set in the child process spawned by
+    /// `an_idle_writer_closes_aggregate_windows`, which owns the global
+    /// privacy setting.
+    const CHILD_AGGREGATE_ENV: &str = "CODEX_LITELLM_TEST_AGGREGATE_WRITER";
+
+    /// Blocks inside `record` until released, standing in for a stalled disk.
+    struct StalledSink {
+        release: mpsc::Receiver<()>,
+    }
+
+    impl TelemetrySink for StalledSink {
+        fn record(&mut self, record: &Value) -> io::Result<()> {
+            if record["stall"] == true {
+                let _ = self.release.recv_timeout(Duration::from_secs(10));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn full_queue_drops_the_oldest_record() {
+        let mut queue = VecDeque::new();
+        let dropped: Vec<bool> = (1..=3)
+            .map(|n| push_bounded(&mut queue, Value::from(n), 2))
+            .collect();
+
+        assert_eq!(dropped, vec![false, false, true]);
+        assert_eq!(queue, VecDeque::from([Value::from(2), Value::from(3)]));
+    }
+
+    #[test]
+    fn a_stalled_sink_does_not_block_callers_and_shutdown_drains() {
+        let (release, stalled) = mpsc::channel();
+        let memory = MemorySink::new(256);
+        register_sink("writer-stalled", Box::new(StalledSink { release: stalled }));
+        register_sink("writer-memory", Box::new(memory.clone()));
+
+        let started = Instant::now();
+        enqueue(vec![serde_json::json!({ "writer_test": 0, "stall": true })]);
+        for n in 1..=20 {
+            enqueue(vec![serde_json::json!({ "writer_test": n })]);
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        release.send(()).expect("release stalled sink");
+        shutdown();
+        assert!(unregister_sink("writer-stalled"));
+        assert!(unregister_sink("writer-memory"));
+
+        let written: Vec<Value> = memory
+            .records()
+            .into_iter()
+            .filter_map(|record| record.get("writer_test").cloned())
+            .collect();
+        assert_eq!(written, (0..=20).map(Value::from).collect::<Vec<_>>());
+    }
+
+    /// Runs only as a child of `an_idle_writer_closes_aggregate_windows`.
+    #[test]
+    fn aggregate_writer_child() {
+        if std::env::var_os(CHILD_AGGREGATE_ENV).is_none() {
+            return;
+        }
+        let memory = MemorySink::new(16);
+        register_sink("aggregate-memory", Box::new(memory.clone()));
+        configure_privacy(Some(PrivacyConfig {
+            mode: PrivacyMode::Aggregate,
+            salt: Some("test-salt".to_string()),
+            rollup_interval_secs: 1,
+        }))
+        .expect("configure privacy");
+
+        record_turn(
+            Some("aggregate-session"),
+            "m",
+            None,
+            None,
+            TurnUsage {
+                total_tokens: 7,
+                ..Default::default()
+            },
+            None,
+        );
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while memory.records().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        let records = memory.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["event"], "rollup");
+        assert_eq!(records[0]["total_tokens"], 7);
+    }
+
+    #[test]
+    fn an_idle_writer_closes_aggregate_windows() {
+        let exe = std::env::current_exe().expect("test binary");
+        let status = Command::new(exe)
+            .args([
+                "--exact",
+                "writer::tests::aggregate_writer_child",
+                "--test-threads=1",
+            ])
+            .env(CHILD_AGGREGATE_ENV, "1")
+            .stdout(Stdio::null())
+            .status()
+            .expect("run child");
+        assert!(status.success());
+    }
+}
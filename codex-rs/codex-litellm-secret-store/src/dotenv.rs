@@ -0,0 +1,131 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::SecretStore;
+
+/// `KEY="value"` lines in a plaintext `.env` file, which Codex loads into
+/// the environment at startup. Other lines are left untouched.
+#[derive(Debug, Clone)]
+pub struct DotenvStore {
+    path: PathBuf,
+}
+
+impl DotenvStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read_lines(&self) -> io::Result<Vec<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(contents.lines().map(ToOwned::to_owned).collect()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn write_lines(&self, lines: &[String]) -> io::Result<()> {
+        let mut rendered = lines.join("\n");
+        rendered.push('\n');
+        fs::write(&self.path, rendered)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+}
+
+fn line_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.strip_prefix(key)?.strip_prefix('=')
+}
+
+impl SecretStore for DotenvStore {
+    fn describe(&self) -> String {
+        format!("{} (plaintext)", self.path.display())
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<String>> {
+        let lines = self.read_lines()?;
+        let Some(raw) = lines.iter().rev().find_map(|line| line_value(line, key)) else {
+            return Ok(None);
+        };
+        // Values are written JSON-quoted; accept bare ones written by hand.
+        Ok(Some(
+            serde_json::from_str::<String>(raw).unwrap_or_else(|_| raw.trim().to_string()),
+        ))
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        let mut lines = self.read_lines()?;
+        let new_line = format!("{key}={}", serde_json::to_string(value)?);
+        match lines
+            .iter()
+            .position(|line| line_value(line, key).is_some())
+        {
+            Some(index) => lines[index] = new_line,
+            None => lines.push(new_line),
+        }
+        self.write_lines(&lines)
+    }
+
+    fn delete(&mut self, key: &str) -> io::Result<bool> {
+        let mut lines = self.read_lines()?;
+        let before = lines.len();
+        lines.retain(|line| line_value(line, key).is_none());
+        if lines.len() == before {
+            return Ok(false);
+        }
+        self.write_lines(&lines)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn updates_its_own_line_and_keeps_the_rest() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join(".env");
+        fs::write(
+            &path,
+            "# gateway\nLITELLM_BASE_URL=https://gw/v1\nOTHER_KEY=x\n",
+        )
+        .expect("seed");
+        let mut store = DotenvStore::new(path.clone());
+
+        store.set("LITELLM_API_KEY", "sk-one").expect("set");
+        store.set("LITELLM_API_KEY", "sk-\"two\"").expect("replace");
+        assert_eq!(
+            store.get("LITELLM_API_KEY").expect("get").as_deref(),
+            Some("sk-\"two\"")
+        );
+        assert_eq!(
+            store
+                .get("LITELLM_BASE_URL")
+                .expect("bare value")
+                .as_deref(),
+            Some("https://gw/v1")
+        );
+
+        assert!(store.delete("LITELLM_API_KEY").expect("delete"));
+        assert!(!store.delete("LITELLM_API_KEY").expect("delete again"));
+        assert_eq!(
+            fs::read_to_string(&path).expect("read"),
+            "# gateway\nLITELLM_BASE_URL=https://gw/v1\nOTHER_KEY=x\n"
+        );
+    }
+}
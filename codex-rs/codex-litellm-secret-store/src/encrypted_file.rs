@@ -0,0 +1,258 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::SecretStore;
+
+const FORMAT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// On-disk layout. The KDF parameters are stored so they can be raised later
+/// without breaking existing files.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct KdfParams {
+    m_cost_kib: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost_kib: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Secrets in one file, encrypted with ChaCha20-Poly1305 under a key derived
+/// from a passphrase with Argon2id. Every write uses a fresh salt and nonce.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    passphrase: Zeroizing<String>,
+    kdf: KdfParams,
+    secrets: BTreeMap<String, Zeroizing<String>>,
+}
+
+impl EncryptedFileStore {
+    /// Opens the file at `path`, or starts an empty store if it does not exist
+    /// yet. A wrong passphrase fails with `PermissionDenied`.
+    pub fn open(path: PathBuf, passphrase: &str) -> io::Result<Self> {
+        if passphrase.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the secrets passphrase must not be empty",
+            ));
+        }
+        let mut store = Self {
+            path,
+            passphrase: Zeroizing::new(passphrase.to_string()),
+            kdf: KdfParams::default(),
+            secrets: BTreeMap::new(),
+        };
+        match fs::read(&store.path) {
+            Ok(bytes) => store.decrypt(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn decrypt(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let envelope: Envelope = serde_json::from_slice(bytes)?;
+        if envelope.version != FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} has format version {}; this build reads {FORMAT_VERSION}",
+                    self.path.display(),
+                    envelope.version
+                ),
+            ));
+        }
+        let salt = decode(&envelope.salt)?;
+        let nonce = decode(&envelope.nonce)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(invalid_data("bad nonce length"));
+        }
+        let cipher = self.cipher(envelope.kdf, &salt)?;
+        let plaintext = Zeroizing::new(
+            cipher
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    decode(&envelope.ciphertext)?.as_slice(),
+                )
+                .map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!(
+                            "wrong passphrase for {} (or the file is damaged)",
+                            self.path.display()
+                        ),
+                    )
+                })?,
+        );
+        let secrets: BTreeMap<String, String> = serde_json::from_slice(&plaintext)?;
+        self.secrets = secrets
+            .into_iter()
+            .map(|(key, value)| (key, Zeroizing::new(value)))
+            .collect();
+        self.kdf = envelope.kdf;
+        Ok(())
+    }
+
+    fn cipher(&self, kdf: KdfParams, salt: &[u8]) -> io::Result<ChaCha20Poly1305> {
+        let params = Params::new(kdf.m_cost_kib, kdf.t_cost, kdf.p_cost, Some(32))
+            .map_err(|err| invalid_data(err.to_string()))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+            .map_err(|err| invalid_data(err.to_string()))?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+        key.zeroize();
+        Ok(cipher)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::getrandom(&mut salt).map_err(|err| io::Error::other(err.to_string()))?;
+        getrandom::getrandom(&mut nonce).map_err(|err| io::Error::other(err.to_string()))?;
+
+        let plain: BTreeMap<&str, &str> = self
+            .secrets
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        let plaintext = Zeroizing::new(serde_json::to_vec(&plain)?);
+        let ciphertext = self
+            .cipher(self.kdf, &salt)?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| io::Error::other("encryption failed"))?;
+        let envelope = Envelope {
+            version: FORMAT_VERSION,
+            kdf: self.kdf,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+
+        // Write beside the target and rename, so a crash never leaves a
+        // half-written file in place of the old one.
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("enc.tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(&envelope)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn describe(&self) -> String {
+        format!("{} (encrypted)", self.path.display())
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<String>> {
+        Ok(self.secrets.get(key).map(|value| value.to_string()))
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        self.secrets
+            .insert(key.to_string(), Zeroizing::new(value.to_string()));
+        self.save()
+    }
+
+    fn delete(&mut self, key: &str) -> io::Result<bool> {
+        if self.secrets.remove(key).is_none() {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+}
+
+fn decode(value: &str) -> io::Result<Vec<u8>> {
+    BASE64
+        .decode(value)
+        .map_err(|err| invalid_data(err.to_string()))
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn round_trips_and_rejects_a_wrong_passphrase() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("litellm-secrets.enc");
+
+        let mut store = EncryptedFileStore::open(path.clone(), "correct horse").expect("create");
+        store.set("LITELLM_API_KEY", "sk-secret-1234").expect("set");
+        store.set("OTHER", "value").expect("set other");
+        assert!(store.delete("OTHER").expect("delete"));
+
+        let on_disk = fs::read_to_string(&path).expect("read");
+        assert!(!on_disk.contains("sk-secret-1234"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).expect("metadata").permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let reopened = EncryptedFileStore::open(path.clone(), "correct horse").expect("reopen");
+        assert_eq!(
+            reopened.get("LITELLM_API_KEY").expect("get").as_deref(),
+            Some("sk-secret-1234")
+        );
+        assert_eq!(reopened.get("OTHER").expect("get other"), None);
+
+        let err = EncryptedFileStore::open(path, "battery staple")
+            .err()
+            .expect("wrong passphrase must fail");
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
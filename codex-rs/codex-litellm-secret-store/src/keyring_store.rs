@@ -0,0 +1,81 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use keyring::credential::CredentialPersistence;
+use keyring::Entry;
+use std::io;
+
+use crate::SecretStore;
+
+/// Keyring service name every secret is stored under; the secret's key is
+/// the keyring user.
+const SERVICE: &str = "codex-litellm";
+
+/// The platform keyring: Secret Service (libsecret) on Linux and BSD,
+/// Keychain on macOS, Credential Manager on Windows.
+#[derive(Debug)]
+pub struct KeyringStore {
+    _private: (),
+}
+
+impl KeyringStore {
+    /// Fails when this platform has no persistent keyring, or when it cannot
+    /// be reached (no Secret Service on a headless box, a locked keychain).
+    pub fn open() -> io::Result<Self> {
+        if !matches!(
+            keyring::default::default_credential_builder().persistence(),
+            CredentialPersistence::UntilDelete
+        ) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "no persistent keyring on this platform",
+            ));
+        }
+        let store = Self { _private: () };
+        // Reading a key that may not exist shows whether the store answers.
+        store.get(crate::LITELLM_API_KEY)?;
+        Ok(store)
+    }
+
+    fn entry(key: &str) -> io::Result<Entry> {
+        Entry::new(SERVICE, key).map_err(keyring_error)
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn describe(&self) -> String {
+        "OS keyring".to_string()
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<String>> {
+        match Self::entry(key)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(keyring_error(err)),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        Self::entry(key)?.set_password(value).map_err(keyring_error)
+    }
+
+    fn delete(&mut self, key: &str) -> io::Result<bool> {
+        match Self::entry(key)?.delete_credential() {
+            Ok(()) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(err) => Err(keyring_error(err)),
+        }
+    }
+}
+
+fn keyring_error(err: keyring::Error) -> io::Error {
+    let kind = match err {
+        keyring::Error::NoStorageAccess(_) => io::ErrorKind::PermissionDenied,
+        keyring::Error::BadEncoding(_) => io::ErrorKind::InvalidData,
+        keyring::Error::TooLong(..) | keyring::Error::Invalid(..) => io::ErrorKind::InvalidInput,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, err)
+}
@@ -0,0 +1,325 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+//! Where codex-litellm keeps the LiteLLM API key: the OS keyring by default,
+//! a passphrase-encrypted file when no keyring is available, or `.env` when
+//! the user explicitly asks for it. Also the no-echo terminal prompts used to
+//! enter it and the encrypted file's passphrase.
+
+use serde::Deserialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+mod dotenv;
+mod encrypted_file;
+mod keyring_store;
+mod mock;
+mod prompt;
+
+pub use dotenv::DotenvStore;
+pub use encrypted_file::EncryptedFileStore;
+pub use keyring_store::KeyringStore;
+pub use mock::MockSecretStore;
+pub use prompt::{fingerprint, prompt_secret, read_secret, secrets_passphrase};
+
+/// Name the LiteLLM API key is stored under in every backend. It matches
+/// the provider's `env_key`, so a key exported in the environment still wins.
+pub const LITELLM_API_KEY: &str = "LITELLM_API_KEY";
+
+/// Passphrase for the encrypted secrets file, for runs without a terminal.
+pub const SECRETS_PASSPHRASE_ENV: &str = "CODEX_LITELLM_SECRETS_PASSPHRASE";
+
+/// File name of the encrypted secrets file under `${CODEX_HOME}`.
+pub const DEFAULT_ENCRYPTED_FILE_NAME: &str = "litellm-secrets.enc";
+
+/// A place to keep secrets by name.
+pub trait SecretStore: Send {
+    /// Short description for messages, e.g. "OS keyring".
+    fn describe(&self) -> String;
+
+    fn get(&self, key: &str) -> io::Result<Option<String>>;
+
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()>;
+
+    /// Returns whether a value was removed.
+    fn delete(&mut self, key: &str) -> io::Result<bool>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretBackend {
+    /// Secret Service (libsecret) on Linux and BSD, Keychain on macOS,
+    /// Credential Manager on Windows. Falls back to `EncryptedFile` where
+    /// none is reachable.
+    #[default]
+    Keyring,
+    EncryptedFile,
+    /// Plaintext `${CODEX_HOME}/.env`. Only used when chosen explicitly.
+    Dotenv,
+}
+
+/// `[litellm.secrets]` in `config.toml`:
+///
+/// ```toml
+/// [litellm.secrets]
+/// backend = "encrypted-file"
+/// path = "/home/me/.codex/litellm-secrets.enc"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct SecretStoreConfig {
+    #[serde(default)]
+    pub backend: SecretBackend,
+    /// Encrypted file location; defaults to
+    /// `${CODEX_HOME}/litellm-secrets.enc`.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+impl SecretStoreConfig {
+    /// Reads `[litellm.secrets]` from a `config.toml`. A missing file or table
+    /// means the defaults.
+    pub fn from_config_toml(path: &Path) -> io::Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        let root: toml::Value = toml::from_str(&contents).map_err(invalid_data)?;
+        match root
+            .get("litellm")
+            .and_then(|litellm| litellm.get("secrets"))
+        {
+            Some(table) => table.clone().try_into().map_err(invalid_data),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn encrypted_file_path(&self, codex_home: &Path) -> PathBuf {
+        self.path
+            .clone()
+            .unwrap_or_else(|| codex_home.join(DEFAULT_ENCRYPTED_FILE_NAME))
+    }
+}
+
+/// Why the encrypted file's passphrase is asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassphrasePurpose {
+    /// The file exists and is about to be decrypted.
+    Unlock,
+    /// The file does not exist yet and will be encrypted with this passphrase.
+    Create,
+}
+
+/// Opens the configured backend. `passphrase` is only called when the
+/// encrypted file is used, including as the keyring fallback.
+pub fn open_secret_store(
+    codex_home: &Path,
+    config: &SecretStoreConfig,
+    passphrase: impl FnOnce(PassphrasePurpose) -> io::Result<String>,
+) -> io::Result<Box<dyn SecretStore>> {
+    match resolve_backend(codex_home, config) {
+        Ok(store) => Ok(store),
+        Err(path) => {
+            let purpose = if path.exists() {
+                PassphrasePurpose::Unlock
+            } else {
+                PassphrasePurpose::Create
+            };
+            Ok(Box::new(EncryptedFileStore::open(
+                path,
+                &passphrase(purpose)?,
+            )?))
+        }
+    }
+}
+
+/// Like `open_secret_store`, but for reading only: when the encrypted file
+/// would be used and does not exist yet, returns `None` without asking for a
+/// passphrase.
+pub fn open_existing_secret_store(
+    codex_home: &Path,
+    config: &SecretStoreConfig,
+    passphrase: impl FnOnce(PassphrasePurpose) -> io::Result<String>,
+) -> io::Result<Option<Box<dyn SecretStore>>> {
+    match resolve_backend(codex_home, config) {
+        Ok(store) => Ok(Some(store)),
+        Err(path) if !path.exists() => Ok(None),
+        Err(path) => Ok(Some(Box::new(EncryptedFileStore::open(
+            path,
+            &passphrase(PassphrasePurpose::Unlock)?,
+        )?))),
+    }
+}
+
+/// The store for backends that need no passphrase, or the path of the
+/// encrypted file to open instead.
+fn resolve_backend(
+    codex_home: &Path,
+    config: &SecretStoreConfig,
+) -> Result<Box<dyn SecretStore>, PathBuf> {
+    match config.backend {
+        SecretBackend::Keyring => match KeyringStore::open() {
+            Ok(store) => return Ok(Box::new(store)),
+            Err(err) => {
+                static WARNED: Once = Once::new();
+                WARNED.call_once(|| {
+                    eprintln!(
+                        "[codex-litellm-secret-store] OS keyring unavailable ({err}); using the encrypted secrets file"
+                    )
+                });
+            }
+        },
+        SecretBackend::Dotenv => return Ok(Box::new(DotenvStore::new(codex_home.join(".env")))),
+        SecretBackend::EncryptedFile => {}
+    }
+    Err(config.encrypted_file_path(codex_home))
+}
+
+/// Name the API key of a named gateway is stored under, e.g.
+/// `LITELLM_API_KEY_STAGING`; `LITELLM_API_KEY` without a gateway. It is a
+/// valid environment variable name, so `.env` files stay loadable, and matches
+/// the gateway's default `api_key_env`.
+pub fn litellm_api_key_name(gateway: Option<&str>) -> String {
+    match gateway {
+        Some(gateway) => format!(
+            "{LITELLM_API_KEY}_{}",
+            gateway.to_ascii_uppercase().replace('-', "_")
+        ),
+        None => LITELLM_API_KEY.to_string(),
+    }
+}
+
+/// The stored LiteLLM API key for `gateway`, if any. Blank values count as
+/// missing.
+pub fn load_litellm_api_key(
+    store: &dyn SecretStore,
+    gateway: Option<&str>,
+) -> io::Result<Option<String>> {
+    Ok(store
+        .get(&litellm_api_key_name(gateway))?
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty()))
+}
+
+fn invalid_data(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn reads_the_backend_from_config_and_defaults_to_the_keyring() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let config = dir.path().join("config.toml");
+        assert_eq!(
+            SecretStoreConfig::from_config_toml(&config).expect("missing file"),
+            SecretStoreConfig::default()
+        );
+
+        std::fs::write(
+            &config,
+            "[litellm.secrets]\nbackend = \"encrypted-file\"\npath = \"/tmp/s.enc\"\n",
+        )
+        .expect("write config");
+        let parsed = SecretStoreConfig::from_config_toml(&config).expect("parse");
+        assert_eq!(parsed.backend, SecretBackend::EncryptedFile);
+        assert_eq!(
+            parsed.encrypted_file_path(dir.path()),
+            PathBuf::from("/tmp/s.enc")
+        );
+    }
+
+    #[test]
+    fn explicit_dotenv_backend_never_asks_for_a_passphrase() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let config = SecretStoreConfig {
+            backend: SecretBackend::Dotenv,
+            path: None,
+        };
+        let mut store = open_secret_store(dir.path(), &config, |_| {
+            panic!("dotenv backend asked for a passphrase")
+        })
+        .expect("open");
+        store.set(LITELLM_API_KEY, " sk-dotenv ").expect("set");
+
+        assert_eq!(
+            load_litellm_api_key(store.as_ref(), None).expect("load"),
+            Some("sk-dotenv".to_string())
+        );
+        assert!(dir.path().join(".env").exists());
+    }
+
+    #[test]
+    fn reading_a_missing_encrypted_file_needs_no_passphrase() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let config = SecretStoreConfig {
+            backend: SecretBackend::EncryptedFile,
+            path: None,
+        };
+        let store =
+            open_existing_secret_store(dir.path(), &config, |_| panic!("nothing to decrypt yet"))
+                .expect("open");
+        assert!(store.is_none());
+
+        open_secret_store(dir.path(), &config, |purpose| {
+            assert_eq!(purpose, PassphrasePurpose::Create);
+            Ok("pass".to_string())
+        })
+        .expect("create")
+        .set(LITELLM_API_KEY, "sk-file")
+        .expect("set");
+        let store = open_existing_secret_store(dir.path(), &config, |purpose| {
+            assert_eq!(purpose, PassphrasePurpose::Unlock);
+            Ok("pass".to_string())
+        })
+        .expect("reopen")
+        .expect("file exists now");
+        assert_eq!(
+            load_litellm_api_key(store.as_ref(), None).expect("load"),
+            Some("sk-file".to_string())
+        );
+    }
+
+    #[test]
+    fn blank_stored_keys_count_as_missing() {
+        let mut store = MockSecretStore::default();
+        assert_eq!(load_litellm_api_key(&store, None).expect("load"), None);
+
+        store.set(LITELLM_API_KEY, "   ").expect("set");
+        assert_eq!(load_litellm_api_key(&store, None).expect("load"), None);
+
+        store.set(LITELLM_API_KEY, "sk-live").expect("set");
+        assert_eq!(
+            load_litellm_api_key(&store, None).expect("load"),
+            Some("sk-live".to_string())
+        );
+    }
+
+    #[test]
+    fn each_gateway_has_its_own_key() {
+        let mut store = MockSecretStore::default();
+        store.set(LITELLM_API_KEY, "sk-default").expect("set");
+        store
+            .set(&litellm_api_key_name(Some("eu-staging")), "sk-staging")
+            .expect("set staging");
+
+        assert_eq!(
+            load_litellm_api_key(&store, Some("eu-staging")).expect("load"),
+            Some("sk-staging".to_string())
+        );
+        assert_eq!(
+            load_litellm_api_key(&store, Some("local")).expect("load"),
+            None
+        );
+        assert_eq!(
+            store.secrets().keys().collect::<Vec<_>>(),
+            vec!["LITELLM_API_KEY", "LITELLM_API_KEY_EU_STAGING"]
+        );
+    }
+}
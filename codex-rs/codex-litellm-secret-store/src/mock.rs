@@ -0,0 +1,49 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::SecretStore;
+
+/// In-memory backend for tests. Clones share the same secrets, so hand one
+/// clone to the code under test and inspect another.
+#[derive(Debug, Clone, Default)]
+pub struct MockSecretStore {
+    secrets: Arc<Mutex<BTreeMap<String, String>>>,
+}
+
+impl MockSecretStore {
+    /// Everything stored, by key.
+    pub fn secrets(&self) -> BTreeMap<String, String> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, String>> {
+        self.secrets
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl SecretStore for MockSecretStore {
+    fn describe(&self) -> String {
+        "mock secret store".to_string()
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<String>> {
+        Ok(self.lock().get(key).cloned())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        self.lock().insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> io::Result<bool> {
+        Ok(self.lock().remove(key).is_some())
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright (c) 2025 Avikalpa Kundu <avi@gour.top>
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+// See the NOTICE file distributed with this work for attribution details.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::{PassphrasePurpose, SECRETS_PASSPHRASE_ENV};
+
+/// Secrets shorter than this are fingerprinted by length only, since their
+/// last characters would give away too much of them.
+const MIN_FINGERPRINT_LEN: usize = 12;
+const FINGERPRINT_CHARS: usize = 4;
+
+/// A short description of a secret that is safe to print, e.g.
+/// `…3f9c (51 characters)`, so the user can tell the right key was entered.
+pub fn fingerprint(secret: &str) -> String {
+    let len = secret.chars().count();
+    if len < MIN_FINGERPRINT_LEN {
+        return format!("({len} characters)");
+    }
+    let tail: String = secret.chars().skip(len - FINGERPRINT_CHARS).collect();
+    format!("…{tail} ({len} characters)")
+}
+
+/// Reads one line from the terminal without echoing it. When the terminal
+/// cannot turn echo off (no tty, an unsupported console), warns and reads a
+/// visible line from stdin instead.
+pub fn read_secret(prompt: &str) -> io::Result<String> {
+    read_secret_with(
+        prompt,
+        &mut |prompt| rpassword::prompt_password(prompt),
+        &mut io::stdin().lock(),
+        &mut io::stderr(),
+    )
+}
+
+/// Asks for a non-empty secret without echo, shows its fingerprint and asks
+/// the user to confirm it, repeating until they do.
+pub fn prompt_secret(label: &str) -> io::Result<String> {
+    prompt_secret_with(
+        label,
+        &mut |prompt| rpassword::prompt_password(prompt),
+        &mut io::stdin().lock(),
+        &mut io::stderr(),
+    )
+}
+
+/// Passphrase for the encrypted secrets file, from
+/// `CODEX_LITELLM_SECRETS_PASSPHRASE` or the terminal. When the file is about
+/// to be created, the terminal prompt asks twice, since a typo would lock the
+/// key away for good.
+pub fn secrets_passphrase(purpose: PassphrasePurpose) -> io::Result<String> {
+    if let Some(passphrase) = std::env::var(SECRETS_PASSPHRASE_ENV)
+        .ok()
+        .filter(|value| !value.is_empty())
+    {
+        return Ok(passphrase);
+    }
+    if !(io::stdin().is_terminal() && io::stderr().is_terminal()) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("set {SECRETS_PASSPHRASE_ENV} to unlock the encrypted LiteLLM secrets file"),
+        ));
+    }
+    secrets_passphrase_with(
+        purpose,
+        &mut |prompt| rpassword::prompt_password(prompt),
+        &mut io::stdin().lock(),
+        &mut io::stderr(),
+    )
+}
+
+type HiddenReader<'a> = dyn FnMut(&str) -> io::Result<String> + 'a;
+
+fn read_secret_with(
+    prompt: &str,
+    hidden: &mut HiddenReader<'_>,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> io::Result<String> {
+    match hidden(prompt) {
+        Ok(secret) => Ok(secret.trim().to_string()),
+        Err(err) => {
+            writeln!(
+                output,
+                "\n(Cannot hide input here: {err}. What you type will be visible.)"
+            )?;
+            write!(output, "{prompt}")?;
+            output.flush()?;
+            read_line(input)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "input ended before a secret was entered",
+                )
+            })
+        }
+    }
+}
+
+fn prompt_secret_with(
+    label: &str,
+    hidden: &mut HiddenReader<'_>,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> io::Result<String> {
+    loop {
+        let secret = read_secret_with(&format!("{label}: "), hidden, input, output)?;
+        if secret.is_empty() {
+            writeln!(output, "{label} cannot be empty.")?;
+            continue;
+        }
+        write!(
+            output,
+            "Received {label} {}. Use it? [Y/n] ",
+            fingerprint(&secret)
+        )?;
+        output.flush()?;
+        match read_line(input)?.as_deref().map(str::to_ascii_lowercase) {
+            None => return Ok(secret),
+            Some(answer) if answer.is_empty() || answer == "y" || answer == "yes" => {
+                return Ok(secret);
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+fn secrets_passphrase_with(
+    purpose: PassphrasePurpose,
+    hidden: &mut HiddenReader<'_>,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> io::Result<String> {
+    loop {
+        let prompt = match purpose {
+            PassphrasePurpose::Unlock => "Passphrase for the encrypted LiteLLM secrets file: ",
+            PassphrasePurpose::Create => "New passphrase for the encrypted LiteLLM secrets file: ",
+        };
+        let passphrase = read_secret_with(prompt, hidden, input, output)?;
+        if passphrase.is_empty() {
+            writeln!(output, "Passphrase cannot be empty.")?;
+            continue;
+        }
+        if purpose == PassphrasePurpose::Unlock {
+            return Ok(passphrase);
+        }
+        let repeated = read_secret_with("Repeat the passphrase: ", hidden, input, output)?;
+        if repeated == passphrase {
+            return Ok(passphrase);
+        }
+        writeln!(output, "The passphrases do not match; try again.")?;
+    }
+}
+
+/// One trimmed line, or `None` at end of input.
+fn read_line(input: &mut dyn BufRead) -> io::Result<Option<String>> {
+    let mut buffer = String::new();
+    if input.read_line(&mut buffer)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(buffer.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn fingerprints_show_only_the_tail_of_long_secrets() {
+        assert_eq!(fingerprint("sk-1234567890abcdef"), "…cdef (19 characters)");
+        assert_eq!(fingerprint("hunter2"), "(7 characters)");
+    }
+
+    #[test]
+    fn falls_back_to_visible_input_and_reprompts_until_confirmed() {
+        let mut hidden = |_: &str| -> io::Result<String> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "no tty"))
+        };
+        // Empty entry, a rejected key, then the confirmed one.
+        let mut input = io::Cursor::new("\nsk-wrong-key-0000\nn\nsk-right-key-1234\n\n");
+        let mut output = Vec::new();
+
+        let secret = prompt_secret_with("LiteLLM API key", &mut hidden, &mut input, &mut output)
+            .expect("prompt");
+
+        assert_eq!(secret, "sk-right-key-1234");
+        let output = String::from_utf8(output).expect("utf8");
+        assert!(output.contains("What you type will be visible."));
+        assert!(output.contains("LiteLLM API key cannot be empty."));
+        assert!(output.contains("Received LiteLLM API key …0000 (17 characters). Use it?"));
+        assert!(!output.contains("sk-right-key-1234"));
+    }
+
+    #[test]
+    fn a_new_passphrase_is_asked_twice_until_both_entries_match() {
+        let mut answers = vec!["", "first-try", "frist-try", "second-try", "second-try"]
+            .into_iter()
+            .map(String::from);
+        let mut prompts = Vec::new();
+        let mut hidden = |prompt: &str| -> io::Result<String> {
+            prompts.push(prompt.to_string());
+            Ok(answers.next().expect("no more answers"))
+        };
+        let mut output = Vec::new();
+
+        let passphrase = secrets_passphrase_with(
+            PassphrasePurpose::Create,
+            &mut hidden,
+            &mut io::Cursor::new(""),
+            &mut output,
+        )
+        .expect("passphrase");
+
+        assert_eq!(passphrase, "second-try");
+        assert_eq!(
+            prompts,
+            vec![
+                "New passphrase for the encrypted LiteLLM secrets file: ",
+                "New passphrase for the encrypted LiteLLM secrets file: ",
+                "Repeat the passphrase: ",
+                "New passphrase for the encrypted LiteLLM secrets file: ",
+                "Repeat the passphrase: ",
+            ]
+        );
+        assert_eq!(
+            String::from_utf8(output).expect("utf8"),
+            "Passphrase cannot be empty.\nThe passphrases do not match; try again.\n"
+        );
+    }
+
+    #[test]
+    fn unlocking_asks_for_the_passphrase_once() {
+        let mut prompts = 0;
+        let mut hidden = |_: &str| -> io::Result<String> {
+            prompts += 1;
+            Ok("unlock-me".to_string())
+        };
+        let passphrase = secrets_passphrase_with(
+            PassphrasePurpose::Unlock,
+            &mut hidden,
+            &mut io::Cursor::new(""),
+            &mut Vec::new(),
+        )
+        .expect("passphrase");
+        assert_eq!((passphrase.as_str(), prompts), ("unlock-me", 1));
+    }
+}